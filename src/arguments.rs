@@ -0,0 +1,114 @@
+use std::path::PathBuf;
+
+use crate::error::{NanoError, NanoResult};
+
+/// Arguments
+/// The command line arguments of the editor:
+/// `nano-rs [-S | --softwrap] [-b | --breaklonglines] [-Y <syntax> | --syntax <syntax>] <file>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Arguments {
+    /// The file to edit
+    pub file: PathBuf,
+    /// The syntax to use instead of the detected one
+    pub syntax: Option<String>,
+    /// Whether long lines are soft wrapped from the start
+    pub softwrap: bool,
+    /// Whether lines typed past the fill column are broken from the start
+    pub break_long_lines: bool,
+}
+
+impl Arguments {
+    /// Parse the arguments, without the program name
+    ///
+    /// # Errors
+    /// This function will return an error if an option is unknown or misses
+    /// its value, or if no file is given.
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> NanoResult<Self> {
+        let mut args = args.into_iter();
+        let mut file = None;
+        let mut syntax = None;
+        let mut softwrap = false;
+        let mut break_long_lines = false;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-Y" | "--syntax" => {
+                    syntax = Some(args.next().ok_or(NanoError::Generic(format!(
+                        "Missing syntax name after {}",
+                        arg
+                    )))?)
+                }
+                "-S" | "--softwrap" => softwrap = true,
+                "-b" | "--breaklonglines" => break_long_lines = true,
+                _ if arg.starts_with("--syntax=") => {
+                    syntax = Some(arg["--syntax=".len()..].to_string())
+                }
+                _ if arg.starts_with('-') && arg.len() > 1 => {
+                    return Err(NanoError::Generic(format!("Unknown option {}", arg)))
+                }
+                _ => file = Some(PathBuf::from(arg)),
+            }
+        }
+
+        Ok(Self {
+            file: file.ok_or(NanoError::FileError(String::from("No file given")))?,
+            syntax,
+            softwrap,
+            break_long_lines,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> NanoResult<Arguments> {
+        Arguments::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let arguments = parse(&["Cargo.toml"]).unwrap();
+        assert_eq!(arguments.file, PathBuf::from("Cargo.toml"));
+        assert_eq!(arguments.syntax, None);
+        assert!(!arguments.softwrap);
+    }
+
+    #[test]
+    fn test_parse_softwrap() {
+        assert!(parse(&["-S", "build"]).unwrap().softwrap);
+        assert!(parse(&["build", "--softwrap"]).unwrap().softwrap);
+    }
+
+    #[test]
+    fn test_parse_break_long_lines() {
+        assert!(!parse(&["build"]).unwrap().break_long_lines);
+        assert!(parse(&["-b", "build"]).unwrap().break_long_lines);
+        assert!(
+            parse(&["build", "--breaklonglines"])
+                .unwrap()
+                .break_long_lines
+        );
+    }
+
+    #[test]
+    fn test_parse_syntax() {
+        let arguments = parse(&["--syntax", "rust", "build"]).unwrap();
+        assert_eq!(arguments.syntax.as_deref(), Some("rust"));
+        assert_eq!(arguments.file, PathBuf::from("build"));
+
+        let arguments = parse(&["build", "-Y", "sh"]).unwrap();
+        assert_eq!(arguments.syntax.as_deref(), Some("sh"));
+
+        let arguments = parse(&["--syntax=toml", "config"]).unwrap();
+        assert_eq!(arguments.syntax.as_deref(), Some("toml"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["build", "--syntax"]).is_err());
+        assert!(parse(&["--frobnicate", "build"]).is_err());
+    }
+}
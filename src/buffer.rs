@@ -2,7 +2,7 @@ use std::fs::File;
 use std::io::Read;
 use std::path::Path;
 
-use crate::content::Data;
+use crate::content::{Data, UnicodeSegmentation};
 use crate::error::NanoResult;
 
 #[derive(Debug, Clone, Default)]
@@ -10,11 +10,14 @@ pub struct Buffer {
     pub name: Option<String>,
     pub data: Vec<Data>,
     pub buffer_type: String,
+    /// Whether the buffer was edited since it was read
+    pub modified: bool,
 }
 
 impl Buffer {
     /// Open a file and create a new FileDocument
-    /// This will open a file and create a new FileDocument from it.
+    /// This will open a file and create a new FileDocument from it. Bytes
+    /// that are not valid UTF-8 are replaced by U+FFFD.
     ///
     /// # Errors
     /// This function will return an error if the file cannot be opened or read.
@@ -25,8 +28,9 @@ impl Buffer {
     ///
     pub fn from_file<P: AsRef<Path>>(file_name: P) -> NanoResult<Self> {
         let mut file = File::open(file_name.as_ref())?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
+        let mut bytes = Vec::new();
+        file.read_to_end(&mut bytes)?;
+        let contents = String::from_utf8_lossy(&bytes);
         let content = contents.lines().map(Data::from).collect::<Vec<_>>();
         let file_type = file_name
             .as_ref()
@@ -38,6 +42,7 @@ impl Buffer {
             name: Some(file_name.as_ref().to_string_lossy().to_string()),
             data: content,
             buffer_type: file_type,
+            modified: false,
         })
     }
 
@@ -61,6 +66,49 @@ impl Buffer {
     pub(crate) fn len(&self) -> usize {
         self.data.len()
     }
+
+    /// Replace `count` lines from `row` with others
+    /// This will return the lines replaced, and marks the buffer modified.
+    pub fn replace_lines(&mut self, row: usize, count: usize, lines: Vec<String>) -> Vec<String> {
+        self.modified = true;
+        self.data
+            .splice(row..row + count, lines.into_iter().map(Data::new))
+            .map(|content| content.text.to_string())
+            .collect()
+    }
+
+    /// Find the next occurrence of `needle` after a position
+    /// The search starts just after `(row, column)` and wraps around to the
+    /// start of the file, columns being grapheme indices. This will return the
+    /// position of the match and whether the search wrapped.
+    pub fn find(&self, needle: &str, row: usize, column: usize) -> Option<((usize, usize), bool)> {
+        let rows = self.len();
+        if needle.is_empty() || rows == 0 {
+            return None;
+        }
+
+        for step in 0..=rows {
+            let index = (row + step) % rows;
+            let text = &self.data[index].text;
+            let from = if step == 0 {
+                text.grapheme_indices(true)
+                    .nth(column + 1)
+                    .map_or(text.len(), |(offset, _)| offset)
+            } else {
+                0
+            };
+
+            if let Some(offset) = text[from..].find(needle) {
+                let found = text[..from + offset].graphemes(true).count();
+                if step == rows && found > column {
+                    return None;
+                }
+                return Some(((index, found), step > 0 && row + step >= rows));
+            }
+        }
+
+        None
+    }
 }
 
 #[cfg(test)]
@@ -73,6 +121,15 @@ mod tests {
         assert_eq!(file.name, Some(String::from("Cargo.toml")));
     }
 
+    #[test]
+    fn test_from_file_with_invalid_bytes() {
+        let path = std::env::temp_dir().join("nano-rs-invalid-bytes.txt");
+        std::fs::write(&path, b"ok\nbad \xff\xfe end\n").unwrap();
+        let file = Buffer::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(file.row(1), Some(&Data::from("bad \u{fffd}\u{fffd} end")));
+    }
+
     #[test]
     fn test_file_document_row() {
         let file = Buffer::from_file("Cargo.toml").unwrap();
@@ -84,4 +141,49 @@ mod tests {
         let file = Buffer::from_file("Cargo.toml").unwrap();
         assert_eq!(file.file_type(), "toml");
     }
+
+    #[test]
+    fn test_replace_lines() {
+        let mut file = Buffer {
+            data: vec![Data::from("a"), Data::from("b"), Data::from("c")],
+            ..Default::default()
+        };
+        let removed = file.replace_lines(1, 1, vec!["x".into(), "y".into()]);
+        assert_eq!(removed, ["b"]);
+        assert_eq!(
+            file.data,
+            [
+                Data::from("a"),
+                Data::from("x"),
+                Data::from("y"),
+                Data::from("c")
+            ]
+        );
+        assert!(file.modified);
+
+        assert!(file.replace_lines(4, 0, vec!["d".into()]).is_empty());
+        assert_eq!(file.row(4), Some(&Data::from("d")));
+    }
+
+    #[test]
+    fn test_find() {
+        let file = Buffer {
+            data: vec![Data::from("foo bar"), Data::from("日本 bar")],
+            ..Default::default()
+        };
+        assert_eq!(file.find("bar", 0, 0), Some(((0, 4), false)));
+        assert_eq!(file.find("bar", 0, 4), Some(((1, 3), false)));
+        assert_eq!(file.find("foo", 1, 0), Some(((0, 0), true)));
+        assert_eq!(file.find("bar", 1, 3), Some(((0, 4), true)));
+        assert_eq!(file.find("baz", 0, 0), None);
+    }
+
+    #[test]
+    fn test_find_only_match_wraps_to_itself() {
+        let file = Buffer {
+            data: vec![Data::from("foo"), Data::from("")],
+            ..Default::default()
+        };
+        assert_eq!(file.find("foo", 0, 0), Some(((0, 0), true)));
+    }
 }
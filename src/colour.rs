@@ -0,0 +1,575 @@
+use std::env;
+
+use crossterm::style::{Color, ContentStyle, StyledContent, Stylize};
+use serde_derive::Deserialize;
+use syntect::highlighting::{Color as ThemeColor, FontStyle, Highlighter, Style, Theme};
+use syntect::parsing::Scope;
+
+/// The 16 ANSI colours, as the xterm palette renders them
+const ANSI_COLOURS: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::DarkRed, (205, 0, 0)),
+    (Color::DarkGreen, (0, 205, 0)),
+    (Color::DarkYellow, (205, 205, 0)),
+    (Color::DarkBlue, (0, 0, 238)),
+    (Color::DarkMagenta, (205, 0, 205)),
+    (Color::DarkCyan, (0, 205, 205)),
+    (Color::Grey, (229, 229, 229)),
+    (Color::DarkGrey, (127, 127, 127)),
+    (Color::Red, (255, 0, 0)),
+    (Color::Green, (0, 255, 0)),
+    (Color::Yellow, (255, 255, 0)),
+    (Color::Blue, (92, 92, 255)),
+    (Color::Magenta, (255, 0, 255)),
+    (Color::Cyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// The colours nano knows by name, as the xterm palette renders them
+const NAMED_COLOURS: &[(&str, ThemeColor)] = &[
+    ("black", rgb(0, 0, 0)),
+    ("red", rgb(205, 0, 0)),
+    ("green", rgb(0, 205, 0)),
+    ("yellow", rgb(205, 205, 0)),
+    ("blue", rgb(0, 0, 238)),
+    ("magenta", rgb(205, 0, 205)),
+    ("cyan", rgb(0, 205, 205)),
+    ("white", rgb(229, 229, 229)),
+    ("brightblack", rgb(127, 127, 127)),
+    ("brightred", rgb(255, 0, 0)),
+    ("brightgreen", rgb(0, 255, 0)),
+    ("brightyellow", rgb(255, 255, 0)),
+    ("brightblue", rgb(92, 92, 255)),
+    ("brightmagenta", rgb(255, 0, 255)),
+    ("brightcyan", rgb(0, 255, 255)),
+    ("brightwhite", rgb(255, 255, 255)),
+    ("grey", rgb(127, 127, 127)),
+    ("gray", rgb(127, 127, 127)),
+];
+
+/// The levels of each component in the 6x6x6 cube of the 256-colour palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+pub const fn rgb(r: u8, g: u8, b: u8) -> ThemeColor {
+    ThemeColor { r, g, b, a: 0xFF }
+}
+
+/// Colour
+/// A foreground and background colour with attributes, written like in
+/// nano: `[bold,][italic,][underline,]foreground[,background]`.
+///
+/// Colours are named (`red`, `brightblue`, `lightred`) or given as `#rgb`.
+/// A missing, empty or `normal` colour leaves the default one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Colour {
+    pub foreground: Option<ThemeColor>,
+    pub background: Option<ThemeColor>,
+    pub font_style: FontStyle,
+}
+
+impl Colour {
+    /// Parse a colour written like in nano
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut font_style = FontStyle::empty();
+        let mut parts = text.split(',').peekable();
+        while let Some(attribute) = parts.peek() {
+            match attribute.trim().to_ascii_lowercase().as_str() {
+                "bold" => font_style |= FontStyle::BOLD,
+                "italic" => font_style |= FontStyle::ITALIC,
+                "underline" => font_style |= FontStyle::UNDERLINE,
+                _ => break,
+            }
+            parts.next();
+        }
+
+        let foreground = parts.next().map(named_colour).transpose()?.flatten();
+        let background = parts.next().map(named_colour).transpose()?.flatten();
+        if parts.next().is_some() {
+            return Err(format!("invalid colour: {}", text));
+        }
+
+        Ok(Self {
+            foreground,
+            background,
+            font_style,
+        })
+    }
+
+    /// Get the style of the colour, the default style filling in what it
+    /// leaves out
+    pub fn style(&self, default: Style) -> Style {
+        Style {
+            foreground: self.foreground.unwrap_or(default.foreground),
+            background: self.background.unwrap_or(default.background),
+            font_style: self.font_style,
+        }
+    }
+}
+
+impl TryFrom<String> for Colour {
+    type Error = String;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        Colour::parse(&text)
+    }
+}
+
+/// Get a colour by name, `light` being a synonym of `bright`
+/// An empty name or `normal` is the default colour.
+fn named_colour(name: &str) -> Result<Option<ThemeColor>, String> {
+    let name = name.trim().to_ascii_lowercase();
+    if name.is_empty() || name == "normal" {
+        return Ok(None);
+    }
+
+    if let Some(hex) = name.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|digit| digit as u8 * 0x11))
+            .collect::<Option<_>>()
+            .ok_or_else(|| format!("invalid colour: {}", name))?;
+        return match digits[..] {
+            [r, g, b] => Ok(Some(rgb(r, g, b))),
+            _ => Err(format!("invalid colour: {}", name)),
+        };
+    }
+
+    let name = match name.strip_prefix("light") {
+        Some(colour) => format!("bright{}", colour),
+        None => name,
+    };
+    NAMED_COLOURS
+        .iter()
+        .find(|(colour, _)| *colour == name)
+        .map(|(_, colour)| Some(*colour))
+        .ok_or_else(|| format!("unknown colour: {}", name))
+}
+
+/// Palette
+/// The colours of the interface around the text: the bars, the messages
+/// and the highlights drawn over the text.
+///
+/// Each colour defaults to one derived from the syntax theme, so that the
+/// interface matches it, and can be set in the configuration.
+#[derive(Debug, Clone)]
+pub struct Palette {
+    /// The colours the terminal can show
+    pub depth: ColourDepth,
+    /// The title bar
+    pub title: Colour,
+    /// Informational messages in the status bar
+    pub status: Colour,
+    /// Warnings in the status bar
+    pub warning: Colour,
+    /// Errors in the status bar
+    pub error: Colour,
+    /// The prompt in the status bar
+    pub prompt: Colour,
+    /// The keys of the shortcut bar
+    pub shortcut: Colour,
+    /// The line number gutter
+    pub numbers: Colour,
+    /// Control characters and invalid bytes in the text
+    pub control: Colour,
+    /// Selected text
+    #[allow(dead_code)] // Reserved until text can be selected
+    pub selection: Colour,
+    /// The match of the last search
+    pub search: Colour,
+}
+
+impl Palette {
+    /// Derive the colours of the interface from a theme
+    /// The bars are drawn in the reverse of the text colours, like nano's
+    /// reverse video, and warnings and errors on the colours the theme gives
+    /// to changed and invalid text.
+    pub fn from_theme(theme: &Theme, depth: ColourDepth) -> Self {
+        let settings = &theme.settings;
+        let foreground = settings.foreground.unwrap_or(rgb(229, 229, 229));
+        let background = settings.background.unwrap_or(rgb(0, 0, 0));
+
+        // The first of the scopes the theme colours apart from plain text
+        let highlighter = Highlighter::new(theme);
+        let scope_colour = |scopes: &[&str], fallback| {
+            scopes
+                .iter()
+                .filter_map(|scope| Scope::new(scope).ok())
+                .map(|scope| highlighter.style_for_stack(&[scope]).foreground)
+                .find(|colour| *colour != foreground)
+                .unwrap_or(fallback)
+        };
+
+        let bar = |colour| Colour {
+            foreground: Some(background),
+            background: Some(colour),
+            font_style: FontStyle::empty(),
+        };
+        let reverse = bar(foreground);
+        let alert = |colour| Colour {
+            font_style: FontStyle::BOLD,
+            ..bar(colour)
+        };
+
+        Self {
+            depth,
+            title: reverse,
+            status: alert(foreground),
+            warning: alert(scope_colour(&["markup.changed"], rgb(205, 205, 0))),
+            error: alert(scope_colour(&["invalid", "markup.deleted"], rgb(205, 0, 0))),
+            prompt: reverse,
+            shortcut: reverse,
+            numbers: Colour {
+                foreground: settings
+                    .gutter_foreground
+                    .or(Some(scope_colour(&["comment"], foreground))),
+                background: settings.gutter,
+                font_style: FontStyle::empty(),
+            },
+            control: Colour {
+                foreground: Some(scope_colour(
+                    &["constant.character.escape", "invalid"],
+                    rgb(205, 0, 205),
+                )),
+                background: None,
+                font_style: FontStyle::BOLD,
+            },
+            selection: Colour {
+                foreground: settings.selection_foreground,
+                background: settings.selection.or(Some(foreground)),
+                font_style: FontStyle::empty(),
+            },
+            search: Colour {
+                foreground: settings.find_highlight_foreground.or(Some(background)),
+                background: settings.find_highlight.or(Some(foreground)),
+                font_style: FontStyle::empty(),
+            },
+        }
+    }
+
+    /// Paint text in one of the colours of the palette
+    pub fn paint(&self, colour: Colour, text: &str) -> StyledContent<String> {
+        StyledContent::new(self.depth.style(colour), text.to_string())
+    }
+}
+
+/// Colour depth
+/// The colours a terminal can show. Theme colours are 24-bit, and are
+/// quantised to the nearest colour of the palette on other terminals.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum ColourDepth {
+    /// 24-bit colours
+    #[serde(rename = "truecolor", alias = "24bit")]
+    TrueColour,
+    /// The xterm 256-colour palette
+    #[serde(rename = "256")]
+    Ansi256,
+    /// The 16 ANSI colours
+    #[serde(rename = "16")]
+    Ansi16,
+    /// No colours at all
+    #[serde(rename = "none")]
+    Monochrome,
+}
+
+impl ColourDepth {
+    /// Detect the colour depth of the terminal from the environment
+    pub fn detect() -> Self {
+        ColourDepth::from_env(
+            env::var("NO_COLOR").ok().as_deref(),
+            env::var("COLORTERM").ok().as_deref(),
+            env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    /// Get the colour depth described by the `NO_COLOR`, `COLORTERM` and
+    /// `TERM` environment variables
+    /// A non-empty `NO_COLOR` disables colours (see <https://no-color.org>),
+    /// and terminals that say nothing of their colours get the 16 ANSI ones.
+    pub fn from_env(no_color: Option<&str>, colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if no_color.is_some_and(|value| !value.is_empty()) {
+            return ColourDepth::Monochrome;
+        }
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            return ColourDepth::TrueColour;
+        }
+
+        match term.unwrap_or_default() {
+            "" | "dumb" => ColourDepth::Monochrome,
+            term if term.ends_with("-direct") || term.contains("truecolor") => {
+                ColourDepth::TrueColour
+            }
+            term if term.contains("256color") => ColourDepth::Ansi256,
+            _ => ColourDepth::Ansi16,
+        }
+    }
+
+    /// Convert a theme colour to the nearest colour the terminal can show
+    pub fn convert(&self, colour: ThemeColor) -> Option<Color> {
+        let rgb = (colour.r, colour.g, colour.b);
+        match self {
+            ColourDepth::TrueColour => Some(Color::Rgb {
+                r: colour.r,
+                g: colour.g,
+                b: colour.b,
+            }),
+            ColourDepth::Ansi256 => Some(Color::AnsiValue(ansi256(rgb))),
+            ColourDepth::Ansi16 => ANSI_COLOURS
+                .iter()
+                .min_by_key(|(_, palette)| distance(rgb, *palette))
+                .map(|(colour, _)| *colour),
+            ColourDepth::Monochrome => None,
+        }
+    }
+
+    /// Get the terminal style of a colour of the interface
+    /// Without colours, a colour with a background is drawn in reverse video
+    /// so that the bars still stand out.
+    pub fn style(&self, colour: Colour) -> ContentStyle {
+        let mut styled = ContentStyle::new();
+        if let Some(foreground) = colour.foreground.and_then(|c| self.convert(c)) {
+            styled = styled.with(foreground);
+        }
+        if let Some(background) = colour.background {
+            styled = match self.convert(background) {
+                Some(background) => styled.on(background),
+                None => styled.reverse(),
+            };
+        }
+
+        if colour.font_style.contains(FontStyle::BOLD) {
+            styled = styled.bold();
+        }
+        if colour.font_style.contains(FontStyle::ITALIC) {
+            styled = styled.italic();
+        }
+        if colour.font_style.contains(FontStyle::UNDERLINE) {
+            styled = styled.underlined();
+        }
+        styled
+    }
+
+    /// Get the terminal style of highlighted text
+    /// Both colours are quantised to the depth. The background is only set
+    /// where it differs from the `default` one of the theme, so that text is
+    /// otherwise drawn on the terminal's. Without colours, text is drawn
+    /// plain, without attributes either.
+    pub fn syntax_style(&self, style: Style, default: Style) -> ContentStyle {
+        if *self == ColourDepth::Monochrome {
+            return ContentStyle::default();
+        }
+
+        let mut styled = ContentStyle {
+            foreground_color: self.convert(style.foreground),
+            ..ContentStyle::default()
+        };
+        if style.background != default.background {
+            styled.background_color = self.convert(style.background);
+        }
+
+        if style.font_style.contains(FontStyle::BOLD) {
+            styled = styled.bold();
+        }
+        if style.font_style.contains(FontStyle::ITALIC) {
+            styled = styled.italic();
+        }
+        if style.font_style.contains(FontStyle::UNDERLINE) {
+            styled = styled.underlined();
+        }
+        styled
+    }
+}
+
+/// Get the nearest colour of the 256-colour palette, from its colour cube
+/// and grey ramp (the first 16 colours vary between terminals)
+fn ansi256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |component: u8| {
+        (0..CUBE_LEVELS.len())
+            .min_by_key(|index| CUBE_LEVELS[*index].abs_diff(component))
+            .unwrap_or_default()
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+
+    // The grey ramp goes from 8 to 238 in steps of 10
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let grey = 8 + step * 10;
+
+    if distance(rgb, (grey, grey, grey)) < distance(rgb, cube) {
+        232 + step
+    } else {
+        16 + 36 * r as u8 + 6 * g as u8 + b as u8
+    }
+}
+
+/// Get the squared distance between two colours
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let component = |a: u8, b: u8| (a.abs_diff(b) as u32).pow(2);
+    component(a.0, b.0) + component(a.1, b.1) + component(a.2, b.2)
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::Attribute;
+    use syntect::highlighting::ThemeSet;
+
+    use super::*;
+
+    #[test]
+    fn test_from_env() {
+        let detect = ColourDepth::from_env;
+        assert_eq!(
+            detect(None, Some("truecolor"), Some("xterm")),
+            ColourDepth::TrueColour
+        );
+        assert_eq!(
+            detect(Some("1"), Some("truecolor"), None),
+            ColourDepth::Monochrome
+        );
+        assert_eq!(
+            detect(Some(""), None, Some("tmux-256color")),
+            ColourDepth::Ansi256
+        );
+        assert_eq!(
+            detect(None, None, Some("xterm-direct")),
+            ColourDepth::TrueColour
+        );
+        assert_eq!(detect(None, None, Some("linux")), ColourDepth::Ansi16);
+        assert_eq!(detect(None, None, Some("dumb")), ColourDepth::Monochrome);
+    }
+
+    #[test]
+    fn test_convert() {
+        let orange = rgb(250, 140, 10);
+        assert_eq!(
+            ColourDepth::TrueColour.convert(orange),
+            Some(Color::Rgb {
+                r: 250,
+                g: 140,
+                b: 10
+            })
+        );
+        assert_eq!(ColourDepth::Ansi16.convert(orange), Some(Color::DarkYellow));
+        assert_eq!(
+            ColourDepth::Ansi16.convert(rgb(30, 30, 30)),
+            Some(Color::Black)
+        );
+        assert_eq!(ColourDepth::Monochrome.convert(orange), None);
+    }
+
+    #[test]
+    fn test_ansi256() {
+        assert_eq!(ansi256((0, 0, 0)), 16);
+        assert_eq!(ansi256((255, 255, 255)), 231);
+        assert_eq!(ansi256((255, 0, 0)), 196);
+        assert_eq!(ansi256((128, 128, 128)), 244);
+        assert_eq!(ansi256((95, 135, 175)), 67);
+    }
+
+    #[test]
+    fn test_syntax_style() {
+        let style = Style {
+            foreground: rgb(255, 0, 0),
+            ..Style::default()
+        };
+        let default = Style::default();
+        assert_eq!(
+            ColourDepth::Monochrome.syntax_style(style, default),
+            ContentStyle::default()
+        );
+        assert_eq!(
+            ColourDepth::Ansi256
+                .syntax_style(style, default)
+                .foreground_color,
+            Some(Color::AnsiValue(196))
+        );
+
+        // The background and attributes are kept, unless the background is
+        // the default one
+        let style = Style {
+            background: rgb(205, 205, 0),
+            font_style: FontStyle::BOLD | FontStyle::UNDERLINE,
+            ..style
+        };
+        let styled = ColourDepth::Ansi16.syntax_style(style, default);
+        assert_eq!(styled.background_color, Some(Color::DarkYellow));
+        assert!(styled.attributes.has(Attribute::Bold));
+        assert!(styled.attributes.has(Attribute::Underlined));
+        assert!(!styled.attributes.has(Attribute::Italic));
+        let styled = ColourDepth::Ansi16.syntax_style(style, style);
+        assert_eq!(styled.background_color, None);
+    }
+
+    #[test]
+    fn test_syntax_style_depths() {
+        let style = Style {
+            foreground: rgb(255, 0, 0),
+            background: rgb(250, 140, 10),
+            font_style: FontStyle::ITALIC,
+        };
+        let styled = |depth: ColourDepth| depth.syntax_style(style, Style::default());
+
+        assert_eq!(
+            styled(ColourDepth::TrueColour).background_color,
+            Some(Color::Rgb {
+                r: 250,
+                g: 140,
+                b: 10
+            })
+        );
+        assert_eq!(
+            styled(ColourDepth::Ansi256).background_color,
+            Some(Color::AnsiValue(ansi256((250, 140, 10))))
+        );
+        assert_eq!(
+            styled(ColourDepth::Ansi16).background_color,
+            Some(Color::DarkYellow)
+        );
+        for depth in [
+            ColourDepth::TrueColour,
+            ColourDepth::Ansi256,
+            ColourDepth::Ansi16,
+        ] {
+            assert!(styled(depth).attributes.has(Attribute::Italic));
+        }
+        assert_eq!(styled(ColourDepth::Monochrome), ContentStyle::default());
+    }
+
+    #[test]
+    fn test_parse_colour() {
+        let colour = Colour::parse("bold,lightred,#0f0").unwrap();
+        assert_eq!(colour.foreground, Some(rgb(255, 0, 0)));
+        assert_eq!(colour.background, Some(rgb(0, 255, 0)));
+        assert_eq!(colour.font_style, FontStyle::BOLD);
+        assert_eq!(Colour::parse(",blue").unwrap().foreground, None);
+        assert!(Colour::parse("purplish").is_err());
+        assert!(Colour::parse("red,blue,green").is_err());
+    }
+
+    #[test]
+    fn test_palette_from_theme() {
+        let themes = ThemeSet::load_defaults();
+        let theme = &themes.themes["base16-mocha.dark"];
+        let palette = Palette::from_theme(theme, ColourDepth::TrueColour);
+        assert_eq!(palette.title.foreground, theme.settings.background);
+        assert_eq!(palette.title.background, theme.settings.foreground);
+        assert_eq!(palette.selection.background, theme.settings.selection);
+        assert_eq!(palette.control.background, None);
+        // The theme has no invalid scope, so errors take the deleted colour
+        assert_eq!(palette.error.background, Some(rgb(203, 96, 119)));
+    }
+
+    #[test]
+    fn test_style() {
+        let colour = Colour::parse("bold,white,blue").unwrap();
+        let style = ColourDepth::Ansi16.style(colour);
+        assert_eq!(style.foreground_color, Some(Color::Grey));
+        assert_eq!(style.background_color, Some(Color::DarkBlue));
+        assert!(style.attributes.has(Attribute::Bold));
+
+        let style = ColourDepth::Monochrome.style(colour);
+        assert_eq!(style.background_color, None);
+        assert!(style.attributes.has(Attribute::Reverse));
+    }
+}
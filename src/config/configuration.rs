@@ -1,37 +1,116 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
 use config::{Config, ConfigError, File};
 use serde_derive::Deserialize;
 use syntect::highlighting::{Theme, ThemeSet};
 
-#[derive(Debug, Deserialize)]
+use crate::colour::{Colour, ColourDepth, Palette};
+use crate::gutter::Gutter;
+use crate::highlight::Engine;
+use crate::terminal::Scrolling;
+use crate::whitespace::Whitespace;
+use crate::wrap::{HardWrap, SoftWrap};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 pub struct NanoConfiguration {
     appearance: AppearanceConfig,
     editor: EditorConfiguration,
 }
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct AppearanceConfig {
     pub theme: String,
+    /// Directory of extra `.sublime-syntax` files, merged with the defaults
+    pub syntax_dir: Option<PathBuf>,
+    /// Directory of extra `.tmTheme` files, merged with the defaults
+    pub theme_dir: Option<PathBuf>,
+    /// Directory of GNU nano `.nanorc` syntax files
+    pub nanorc_dir: Option<PathBuf>,
+    /// The highlighting engine of each file type, syntect by default
+    pub engines: HashMap<String, Engine>,
+    /// The colours of the terminal, detected from the environment by
+    /// default
+    pub colour_depth: Option<ColourDepth>,
+    /// The colours of the interface, derived from the theme by default
+    pub colours: InterfaceColours,
 }
 
-#[derive(Debug, Clone, Deserialize, Default)]
+/// The colours of the interface set in the configuration, each replacing
+/// the one derived from the theme
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct InterfaceColours {
+    pub title: Option<Colour>,
+    pub status: Option<Colour>,
+    pub warning: Option<Colour>,
+    pub error: Option<Colour>,
+    pub prompt: Option<Colour>,
+    pub shortcut: Option<Colour>,
+    pub numbers: Option<Colour>,
+    pub control: Option<Colour>,
+    pub selection: Option<Colour>,
+    pub search: Option<Colour>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
 pub struct EditorConfiguration {
     pub line_numbers: bool,
+    /// Number the lines relative to the line of the cursor
+    pub relative_line_numbers: bool,
+    /// The number of lines kept in view above and below the cursor
+    pub scroll_margin: u16,
+    /// Scroll by half a screen rather than line by line
+    pub jumpy_scrolling: bool,
+    /// The number of columns between tab stops
+    pub tabsize: usize,
+    /// The glyphs tabs and spaces are shown as, in that order
+    pub whitespace: String,
+    /// Show tabs and spaces from the start (toggled with Alt+P)
+    pub show_whitespace: bool,
+    /// Highlight whitespace at the end of lines
+    pub trailing_whitespace: bool,
+    /// Show long lines across several rows (toggled with Alt+S)
+    pub softwrap: bool,
+    /// Break soft wrapped lines after blanks rather than at the last column
+    pub wrap_at_blanks: bool,
+    /// Break lines typed past the fill column (toggled with Alt+L)
+    pub break_long_lines: bool,
+    /// The column past which typed lines are broken
+    pub fill: usize,
 }
 
 impl NanoConfiguration {
+    /// Load the configuration
+    /// The defaults are used when the configuration cannot be parsed, it
+    /// should never prevent the editor from starting.
     pub fn load() -> Self {
-        todo!()
+        NanoConfiguration::parse_config().unwrap_or_else(|e| {
+            log::error!("Failed to load configuration, using defaults: {}", e);
+            NanoConfiguration::default()
+        })
     }
+
+    /// Parse the configuration
+    /// This reads `nano.toml` from the configuration directory, then from
+    /// the current directory, the latter taking precedence.
     pub fn parse_config() -> Result<Self, ConfigError> {
-        let config = Config::builder()
-            .add_source(File::with_name("nano").required(true))
+        let mut builder = Config::builder();
+        if let Some(dir) = NanoConfiguration::config_dir() {
+            builder = builder.add_source(File::from(dir.join("nano")).required(false));
+        }
+        let config = builder
+            .add_source(File::with_name("nano").required(false))
             .build()?;
 
         let data: NanoConfiguration = config.try_deserialize()?;
         Ok(data)
     }
 
-    pub fn load_themes(&self) -> Result<Theme, ConfigError> {
-        let theme_set = ThemeSet::load_defaults();
+    /// Get the configured theme from a theme set
+    pub fn load_theme(&self, theme_set: &ThemeSet) -> Result<Theme, ConfigError> {
         let theme = theme_set
             .themes
             .get(&self.appearance.theme)
@@ -42,25 +121,196 @@ impl NanoConfiguration {
             .clone();
         Ok(theme)
     }
+
+    /// Get the directory of extra syntax definitions
+    /// This is `syntaxes` in the configuration directory unless configured.
+    pub fn syntax_dir(&self) -> Option<PathBuf> {
+        match &self.appearance.syntax_dir {
+            Some(dir) => Some(expand_home(dir)),
+            None => NanoConfiguration::config_dir().map(|dir| dir.join("syntaxes")),
+        }
+    }
+
+    /// Get the directory of extra themes
+    /// This is `themes` in the configuration directory unless configured.
+    pub fn theme_dir(&self) -> Option<PathBuf> {
+        match &self.appearance.theme_dir {
+            Some(dir) => Some(expand_home(dir)),
+            None => NanoConfiguration::config_dir().map(|dir| dir.join("themes")),
+        }
+    }
+
+    /// Get the directory of nanorc syntax files
+    /// This is `nanorc` in the configuration directory unless configured.
+    pub fn nanorc_dir(&self) -> Option<PathBuf> {
+        match &self.appearance.nanorc_dir {
+            Some(dir) => Some(expand_home(dir)),
+            None => NanoConfiguration::config_dir().map(|dir| dir.join("nanorc")),
+        }
+    }
+
+    /// Get the highlighting engine of a file type
+    pub fn engine(&self, file_type: &str) -> Engine {
+        self.appearance
+            .engines
+            .get(file_type)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Get the colour depth of the terminal
+    /// The configured depth takes precedence over the one detected.
+    pub fn colour_depth(&self) -> ColourDepth {
+        self.appearance
+            .colour_depth
+            .unwrap_or_else(ColourDepth::detect)
+    }
+
+    /// Get the colours of the interface for a theme
+    /// The configured colours take precedence over those derived from the
+    /// theme.
+    pub fn palette(&self, theme: &Theme) -> Palette {
+        let mut palette = Palette::from_theme(theme, self.colour_depth());
+        let colours = &self.appearance.colours;
+        for (colour, configured) in [
+            (&mut palette.title, colours.title),
+            (&mut palette.status, colours.status),
+            (&mut palette.warning, colours.warning),
+            (&mut palette.error, colours.error),
+            (&mut palette.prompt, colours.prompt),
+            (&mut palette.shortcut, colours.shortcut),
+            (&mut palette.numbers, colours.numbers),
+            (&mut palette.control, colours.control),
+            (&mut palette.selection, colours.selection),
+            (&mut palette.search, colours.search),
+        ] {
+            if let Some(configured) = configured {
+                *colour = configured;
+            }
+        }
+        palette
+    }
+
+    /// Get the line number gutter
+    pub fn gutter(&self) -> Gutter {
+        Gutter {
+            shown: self.editor.line_numbers,
+            relative: self.editor.relative_line_numbers,
+        }
+    }
+
+    /// Get how the view follows the cursor
+    pub fn scrolling(&self) -> Scrolling {
+        Scrolling {
+            margin: self.editor.scroll_margin,
+            smooth: !self.editor.jumpy_scrolling,
+        }
+    }
+
+    /// Get the number of columns between tab stops, at least one
+    pub fn tabsize(&self) -> usize {
+        self.editor.tabsize.max(1)
+    }
+
+    /// Get how tabs and spaces are shown
+    /// The default glyphs are used when the configured ones are invalid.
+    pub fn whitespace(&self) -> Whitespace {
+        let mut whitespace = Whitespace::default();
+        whitespace.shown = self.editor.show_whitespace;
+        whitespace.trailing = self.editor.trailing_whitespace;
+        if let Err(e) = whitespace.set_glyphs(&self.editor.whitespace) {
+            log::error!("Invalid configuration, using default: {}", e);
+        }
+        whitespace
+    }
+
+    /// Get how long lines are soft wrapped
+    pub fn softwrap(&self) -> SoftWrap {
+        SoftWrap {
+            enabled: self.editor.softwrap,
+            at_blanks: self.editor.wrap_at_blanks,
+        }
+    }
+
+    /// Get how lines typed past the fill column are broken
+    pub fn hard_wrap(&self) -> HardWrap {
+        HardWrap {
+            enabled: self.editor.break_long_lines,
+            fill: self.editor.fill.max(1),
+        }
+    }
+
+    /// Get the directory of the configuration
+    /// This is `$XDG_CONFIG_HOME/nano-rs` on Linux.
+    pub fn config_dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("nano-rs"))
+    }
+
+    /// Get the directory where state is kept between sessions
+    /// This is `$XDG_DATA_HOME/nano-rs` on Linux.
+    pub fn data_dir() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("nano-rs"))
+    }
+
+    /// Get the directory where generated files are cached
+    /// This is `$XDG_CACHE_HOME/nano-rs` on Linux.
+    pub fn cache_dir() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("nano-rs"))
+    }
 }
 
-impl Default for NanoConfiguration {
+impl Default for AppearanceConfig {
     fn default() -> Self {
         Self {
-            appearance: AppearanceConfig {
-                theme: "Monokai".to_string(),
-            },
-            editor: EditorConfiguration {
-                line_numbers: false,
-            },
+            theme: "base16-mocha.dark".to_string(),
+            syntax_dir: None,
+            theme_dir: None,
+            nanorc_dir: None,
+            engines: HashMap::new(),
+            colour_depth: None,
+            colours: InterfaceColours::default(),
         }
     }
 }
 
+impl Default for EditorConfiguration {
+    fn default() -> Self {
+        Self {
+            line_numbers: false,
+            relative_line_numbers: false,
+            scroll_margin: 0,
+            jumpy_scrolling: false,
+            tabsize: 8,
+            whitespace: String::from("»·"),
+            show_whitespace: false,
+            trailing_whitespace: false,
+            softwrap: false,
+            wrap_at_blanks: false,
+            break_long_lines: false,
+            fill: 72,
+        }
+    }
+}
+
+/// Expand a leading `~` to the home directory
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
 #[cfg(test)]
 mod test {
 
-    use super::NanoConfiguration;
+    use std::path::{Path, PathBuf};
+
+    use config::{Config, File, FileFormat};
+    use syntect::highlighting::ThemeSet;
+
+    use super::{expand_home, NanoConfiguration};
+    use crate::colour::{Colour, ColourDepth};
+    use crate::highlight::Engine;
 
     #[test]
     fn parse_config() {
@@ -70,7 +320,7 @@ mod test {
     #[test]
     fn load() {
         let config = NanoConfiguration::parse_config().unwrap();
-        match config.load_themes() {
+        match config.load_theme(&ThemeSet::load_defaults()) {
             Ok(theme) => {
                 assert_eq!(theme.name, Some("Base16 Mocha Dark".to_string()))
             }
@@ -83,4 +333,106 @@ mod test {
         let turn_on = config.editor.line_numbers;
         assert_eq!(turn_on, true);
     }
+
+    #[test]
+    fn test_asset_dirs_default_to_config_dir() {
+        let config = NanoConfiguration::default();
+        let config_dir = NanoConfiguration::config_dir();
+        assert_eq!(
+            config.syntax_dir(),
+            config_dir.map(|dir| dir.join("syntaxes"))
+        );
+    }
+
+    #[test]
+    fn test_engine_per_file_type() {
+        let mut config = NanoConfiguration::default();
+        config
+            .appearance
+            .engines
+            .insert("c".to_string(), Engine::Nanorc);
+        assert_eq!(config.engine("c"), Engine::Nanorc);
+        assert_eq!(config.engine("rs"), Engine::Syntect);
+    }
+
+    #[test]
+    fn test_colour_depth_override() {
+        let mut config = NanoConfiguration::default();
+        config.appearance.colour_depth = Some(ColourDepth::Ansi256);
+        assert_eq!(config.colour_depth(), ColourDepth::Ansi256);
+    }
+
+    #[test]
+    fn test_configured_colours() {
+        let mut config = NanoConfiguration::default();
+        let title = Colour::parse("bold,white,blue").unwrap();
+        config.appearance.colours.title = Some(title);
+        let theme = config.load_theme(&ThemeSet::load_defaults()).unwrap();
+
+        let palette = config.palette(&theme);
+        assert_eq!(palette.title, title);
+        assert_eq!(palette.status.background, theme.settings.foreground);
+    }
+
+    #[test]
+    fn test_parse_colours() {
+        let config: NanoConfiguration = Config::builder()
+            .add_source(File::from_str(
+                "[appearance.colours]\nerror = \"bold,white,red\"",
+                FileFormat::Toml,
+            ))
+            .build()
+            .and_then(Config::try_deserialize)
+            .unwrap();
+        assert_eq!(
+            config.appearance.colours.error,
+            Some(Colour::parse("bold,white,red").unwrap())
+        );
+
+        let invalid = Config::builder()
+            .add_source(File::from_str(
+                "[appearance.colours]\nerror = \"purplish\"",
+                FileFormat::Toml,
+            ))
+            .build()
+            .and_then(Config::try_deserialize::<NanoConfiguration>);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_tabsize() {
+        let mut config = NanoConfiguration::default();
+        assert_eq!(config.tabsize(), 8);
+        config.editor.tabsize = 0;
+        assert_eq!(config.tabsize(), 1);
+    }
+
+    #[test]
+    fn test_hard_wrap() {
+        let mut config = NanoConfiguration::default();
+        assert!(!config.hard_wrap().enabled);
+        assert_eq!(config.hard_wrap().fill, 72);
+        config.editor.fill = 0;
+        assert_eq!(config.hard_wrap().fill, 1);
+    }
+
+    #[test]
+    fn test_whitespace() {
+        let mut config = NanoConfiguration::default();
+        config.editor.show_whitespace = true;
+        assert_eq!(config.whitespace().tab(2), "» ");
+
+        config.editor.whitespace = String::from("too long");
+        assert_eq!(config.whitespace().space(), "·");
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = dirs::home_dir().unwrap();
+        assert_eq!(expand_home(Path::new("~/themes")), home.join("themes"));
+        assert_eq!(
+            expand_home(Path::new("/etc/themes")),
+            PathBuf::from("/etc/themes")
+        );
+    }
 }
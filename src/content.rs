@@ -1,19 +1,63 @@
+use std::fmt;
+use std::ops::Deref;
+use std::sync::Arc;
+
 pub use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Text
+/// The text of a row, shared by the copies of the row: cloning a buffer, as
+/// is done for the highlight worker, copies no text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Text(Arc<str>);
+
+impl Text {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Text {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<&str> for Text {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
 
 /// Content
 /// This struct is used to store the content of a row.
 /// It is a wrapper around a string, and it also stores the length of the
-/// string.
+/// string in bytes.
+///
+/// A position in a row is one of three coordinates: a byte offset into
+/// `text`, a grapheme index, which is where the cursor can be, and a display
+/// column, which is where it is shown. East Asian wide characters and emoji,
+/// including ZWJ sequences such as "👨\u{200d}👩\u{200d}👧", take two columns,
+/// and combining marks none of their own. A tab reaches the next tab stop,
+/// every `tabsize` columns.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct Data {
-    pub text: String,
+    pub text: Text,
     len: usize,
 }
 
 impl From<&str> for Data {
     fn from(text: &str) -> Self {
         Self {
-            text: text.graphemes(true).collect(),
+            text: Text(text.into()),
             len: text.len(),
         }
     }
@@ -22,14 +66,14 @@ impl From<&str> for Data {
 impl Data {
     pub fn new(text: String) -> Self {
         Self {
-            text: text.clone(),
             len: text.len(),
+            text: Text(text.into()),
         }
     }
     /// Display the content
     /// This will return the content as a string.
     pub fn display(&self) -> String {
-        self.text.clone()
+        self.text.to_string()
     }
 
     /// Get a range of the content
@@ -42,21 +86,166 @@ impl Data {
             .collect()
     }
 
-    /// Create a new Content
+    /// Get the length of the content in bytes
     pub fn len(&self) -> usize {
         self.len
     }
 
+    /// Get the number of graphemes of the content, the columns the cursor
+    /// can be at being `0..=grapheme_count()`
+    pub fn grapheme_count(&self) -> usize {
+        self.text.graphemes(true).count()
+    }
+
     /// Check if the content is empty
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
+
+    /// Get the number of columns the content is shown in
+    pub fn width(&self, tabsize: usize) -> usize {
+        self.column(usize::MAX, tabsize)
+    }
+
+    /// Get the byte offset of a grapheme, the end of the text past its last
+    pub fn byte_offset(&self, index: usize) -> usize {
+        self.text
+            .grapheme_indices(true)
+            .nth(index)
+            .map_or(self.text.len(), |(offset, _)| offset)
+    }
+
+    /// Get the index of the grapheme containing a byte offset
+    pub fn grapheme_index(&self, offset: usize) -> usize {
+        self.text
+            .grapheme_indices(true)
+            .take_while(|(start, grapheme)| start + grapheme.len() <= offset)
+            .count()
+    }
+
+    /// Get the display column a grapheme starts at
+    pub fn column(&self, index: usize, tabsize: usize) -> usize {
+        self.text
+            .graphemes(true)
+            .take(index)
+            .fold(0, |column, grapheme| {
+                column + Glyph::new(grapheme, column, tabsize).width()
+            })
+    }
+
+    /// Get the index of the grapheme shown at a display column
+    /// A column in the middle of a wide grapheme gives that grapheme, and
+    /// one past the end of the text the number of graphemes.
+    pub fn grapheme_at_column(&self, column: usize, tabsize: usize) -> usize {
+        let mut end = 0;
+        self.text
+            .graphemes(true)
+            .take_while(|grapheme| {
+                end += Glyph::new(grapheme, end, tabsize).width();
+                end <= column
+            })
+            .count()
+    }
+}
+
+/// Glyph
+/// How a grapheme of the text is shown on the screen.
+///
+/// Tabs and control characters would break the layout if written to the
+/// terminal, so they are expanded to spaces and shown as `^X`. Invalid
+/// bytes, replaced by U+FFFD when the file is read, are shown as that
+/// placeholder, and both stand out in the control colour.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Glyph<'a> {
+    /// A grapheme shown as it is
+    Text(&'a str),
+    /// A tab, shown as spaces up to the next tab stop
+    Tab(usize),
+    /// A control character or invalid bytes, shown escaped
+    Escaped(String),
+}
+
+impl<'a> Glyph<'a> {
+    /// Get how a grapheme is shown from a display column
+    pub fn new(grapheme: &'a str, column: usize, tabsize: usize) -> Self {
+        let mut chars = grapheme.chars();
+        match (chars.next(), chars.next()) {
+            (Some('\t'), None) => Glyph::Tab(tabsize - column % tabsize),
+            (Some(REPLACEMENT_CHARACTER), None) => {
+                Glyph::Escaped(REPLACEMENT_CHARACTER.to_string())
+            }
+            (Some(control), None) if control.is_control() => {
+                // ^@ to ^_ and ^? for C0 and DEL, the same letters for C1
+                let caret = (control as u8 & 0x1f) + 0x40;
+                let caret = if control == '\x7f' {
+                    '?'
+                } else {
+                    caret as char
+                };
+                Glyph::Escaped(format!("^{}", caret))
+            }
+            _ => Glyph::Text(grapheme),
+        }
+    }
+
+    /// Get the number of columns the glyph takes
+    pub fn width(&self) -> usize {
+        match self {
+            Glyph::Text(grapheme) => width(grapheme),
+            Glyph::Tab(width) => *width,
+            Glyph::Escaped(text) => text.width(),
+        }
+    }
+}
+
+/// The placeholder of invalid bytes
+const REPLACEMENT_CHARACTER: char = '\u{fffd}';
+
+/// Get the number of columns a grapheme is shown in
+/// Every grapheme takes at least one column, so that the cursor can be
+/// shown on it, and wide ones two.
+pub fn width(grapheme: &str) -> usize {
+    grapheme.width().clamp(1, 2)
+}
+
+/// Get the number of columns a text is shown in, tabs aside
+pub fn text_width(text: &str) -> usize {
+    text.graphemes(true).map(width).sum()
+}
+
+/// Cut a text to the graphemes shown in the given number of columns
+/// This will return the text kept and the number of columns it takes, which
+/// is one less than asked when a wide grapheme does not fit.
+pub fn fit(text: &str, columns: usize) -> (&str, usize) {
+    let mut used = 0;
+    for (offset, grapheme) in text.grapheme_indices(true) {
+        if used + width(grapheme) > columns {
+            return (&text[..offset], used);
+        }
+        used += width(grapheme);
+    }
+    (text, used)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_fit() {
+        assert_eq!(text_width("a日本"), 5);
+        assert_eq!(fit("a日本", 4), ("a日", 3));
+        assert_eq!(fit("a日本", 5), ("a日本", 5));
+        assert_eq!(fit("e\u{301}x", 1), ("e\u{301}", 1));
+    }
+
+    #[test]
+    fn test_clone_shares_text() {
+        let content = Data::from("Hello, world!");
+        let copy = content.clone();
+        assert!(Arc::ptr_eq(&content.text.0, &copy.text.0));
+    }
+
     #[test]
     fn test_content_from_str() {
         let content = Data::from("Hello, world!");
@@ -114,6 +303,71 @@ mod tests {
         assert_eq!(content.len(), 16);
     }
 
+    #[test]
+    fn test_grapheme_count() {
+        assert_eq!(Data::from("日本語").grapheme_count(), 3);
+        assert_eq!(
+            Data::from("e\u{301}👨\u{200d}👩\u{200d}👧").grapheme_count(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_width() {
+        assert_eq!(width("a"), 1);
+        assert_eq!(width("日"), 2);
+        assert_eq!(width("e\u{301}"), 1);
+        assert_eq!(width("👨\u{200d}👩\u{200d}👧"), 2);
+        assert_eq!(Data::from("a日本語").width(8), 7);
+    }
+
+    #[test]
+    fn test_glyph() {
+        assert_eq!(Glyph::new("a", 0, 8), Glyph::Text("a"));
+        assert_eq!(Glyph::new("\t", 3, 8), Glyph::Tab(5));
+        assert_eq!(Glyph::new("\t", 8, 4), Glyph::Tab(4));
+        assert_eq!(Glyph::new("\x01", 0, 8), Glyph::Escaped("^A".into()));
+        assert_eq!(Glyph::new("\x1b", 0, 8), Glyph::Escaped("^[".into()));
+        assert_eq!(Glyph::new("\x7f", 0, 8), Glyph::Escaped("^?".into()));
+        assert_eq!(Glyph::new("\u{9b}", 0, 8), Glyph::Escaped("^[".into()));
+        assert_eq!(Glyph::new("\u{fffd}", 0, 8).width(), 1);
+    }
+
+    #[test]
+    fn test_columns_with_tabs() {
+        let content = Data::from("a\tb\x01\tc");
+        let columns = [0, 1, 4, 5, 7, 8, 9];
+        for (index, column) in columns.into_iter().enumerate() {
+            assert_eq!(content.column(index, 4), column);
+            assert_eq!(content.grapheme_at_column(column, 4), index);
+        }
+        assert_eq!(content.grapheme_at_column(2, 4), 1);
+        assert_eq!(content.width(8), 17);
+    }
+
+    #[test]
+    fn test_coordinates() {
+        let content = Data::from("a日e\u{301}👨\u{200d}👩\u{200d}👧b");
+
+        let offsets = [0, 1, 4, 7, 25, 26];
+        let columns = [0, 1, 3, 4, 6, 7];
+        for (index, (offset, column)) in offsets.into_iter().zip(columns).enumerate() {
+            assert_eq!(content.byte_offset(index), offset);
+            assert_eq!(content.grapheme_index(offset), index);
+            assert_eq!(content.column(index, 8), column);
+            assert_eq!(content.grapheme_at_column(column, 8), index);
+        }
+
+        // Inside a grapheme
+        assert_eq!(content.grapheme_index(2), 1);
+        assert_eq!(content.grapheme_index(10), 3);
+        assert_eq!(content.grapheme_at_column(2, 8), 1);
+        assert_eq!(content.grapheme_at_column(5, 8), 3);
+        // Past the end
+        assert_eq!(content.byte_offset(9), 26);
+        assert_eq!(content.grapheme_at_column(40, 8), 5);
+    }
+
     #[test]
     fn test_is_empty() {
         let content = Data::new("".into());
@@ -0,0 +1,269 @@
+use crate::buffer::Buffer;
+use crate::content::Data;
+use crate::terminal::Location;
+use crate::wrap::{self, Chunk};
+
+/// Movement
+/// A step of the cursor through the buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    Left,
+    Right,
+    Up,
+    Down,
+    /// To the start of the line
+    Home,
+    /// To the end of the line
+    End,
+}
+
+/// Cursor
+/// The position of the cursor in the buffer, in lines and graphemes, and the
+/// display column it keeps when moving up and down (nano's `placewewant`).
+///
+/// Moving through a line shorter than the desired column puts the cursor at
+/// its end, and the next longer line brings it back to the desired column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub x: usize,
+    pub y: usize,
+    desired: usize,
+}
+
+impl Cursor {
+    /// Place the cursor at a position, clamped to the buffer
+    pub fn new(buffer: &Buffer, x: usize, y: usize, tabsize: usize) -> Self {
+        let y = y.min(buffer.len().saturating_sub(1));
+        let x = x.min(line_length(buffer, y));
+        let mut cursor = Self { x, y, desired: 0 };
+        cursor.desired = cursor.column(buffer, tabsize);
+        cursor
+    }
+
+    /// Get where the cursor is shown, its column in display columns
+    /// Wide graphemes before the cursor, such as "日本語", push it further
+    /// right than its grapheme index, as do tabs and control characters.
+    pub fn position(&self, buffer: &Buffer, tabsize: usize) -> Location {
+        Location {
+            x: self.column(buffer, tabsize),
+            y: self.y,
+        }
+    }
+
+    fn column(&self, buffer: &Buffer, tabsize: usize) -> usize {
+        buffer
+            .row(self.y)
+            .map_or(0, |content| content.column(self.x, tabsize))
+    }
+
+    /// Move the cursor a step through the buffer
+    /// Left at the start of a line goes to the end of the previous one, and
+    /// right at the end of a line to the start of the next one.
+    pub fn move_by(&mut self, buffer: &Buffer, movement: Movement, tabsize: usize) {
+        let length = line_length(buffer, self.y);
+        let last = buffer.len().saturating_sub(1);
+
+        match movement {
+            Movement::Left if self.x > 0 => self.x -= 1,
+            Movement::Left if self.y > 0 => {
+                self.y -= 1;
+                self.x = line_length(buffer, self.y);
+            }
+            Movement::Right if self.x < length => self.x += 1,
+            Movement::Right if self.y < last => {
+                self.y += 1;
+                self.x = 0;
+            }
+            Movement::Up if self.y > 0 => {
+                self.y -= 1;
+                self.x = at_column(buffer, self.y, self.desired, tabsize);
+                return;
+            }
+            Movement::Down if self.y < last => {
+                self.y += 1;
+                self.x = at_column(buffer, self.y, self.desired, tabsize);
+                return;
+            }
+            Movement::Up | Movement::Down => return,
+            Movement::Home => self.x = 0,
+            Movement::End => self.x = length,
+            Movement::Left | Movement::Right => {}
+        }
+        self.desired = self.column(buffer, tabsize);
+    }
+
+    /// Move the cursor up or down a row of the screen, lines being soft
+    /// wrapped into the chunks given by `chunks`
+    /// The cursor keeps its column within the rows, like in nano.
+    pub fn move_visually(
+        &mut self,
+        buffer: &Buffer,
+        movement: Movement,
+        tabsize: usize,
+        chunks: impl Fn(&Data) -> Vec<Chunk>,
+    ) {
+        let chunks_of = |y| {
+            buffer
+                .row(y)
+                .map_or_else(|| vec![Chunk::default()], &chunks)
+        };
+        let current = chunks_of(self.y);
+        let chunk = wrap::chunk_of(&current, self.x);
+        let target = self.desired.saturating_sub(current[chunk].column);
+
+        let (y, chunks, chunk) = match movement {
+            Movement::Up if chunk > 0 => (self.y, current, chunk - 1),
+            Movement::Up if self.y > 0 => {
+                let previous = chunks_of(self.y - 1);
+                let last = previous.len() - 1;
+                (self.y - 1, previous, last)
+            }
+            Movement::Down if chunk + 1 < current.len() => (self.y, current, chunk + 1),
+            Movement::Down if self.y + 1 < buffer.len() => (self.y + 1, chunks_of(self.y + 1), 0),
+            _ => return,
+        };
+
+        // The cursor stays on the row, short of the grapheme starting the next
+        let column = chunks[chunk].column + target;
+        let end = chunks
+            .get(chunk + 1)
+            .map_or(line_length(buffer, y), |next| next.start - 1);
+        self.y = y;
+        self.x = at_column(buffer, y, column, tabsize).clamp(chunks[chunk].start, end);
+        self.desired = column;
+    }
+}
+
+/// Get the number of graphemes of a line, none past the end of the buffer
+fn line_length(buffer: &Buffer, y: usize) -> usize {
+    buffer.row(y).map_or(0, |content| content.grapheme_count())
+}
+
+/// Get the grapheme of a line shown at a display column
+fn at_column(buffer: &Buffer, y: usize, column: usize, tabsize: usize) -> usize {
+    buffer
+        .row(y)
+        .map_or(0, |content| content.grapheme_at_column(column, tabsize))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wrap::SoftWrap;
+
+    fn buffer(lines: &[&str]) -> Buffer {
+        Buffer {
+            data: lines.iter().map(|line| Data::from(*line)).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_new_clamps_to_buffer() {
+        let buffer = buffer(&["日本語", "ab"]);
+        assert_eq!(Cursor::new(&buffer, 9, 0, 8).x, 3);
+        assert_eq!(Cursor::new(&buffer, 9, 5, 8).y, 1);
+        assert_eq!(Cursor::new(&Buffer::default(), 4, 4, 8), Cursor::default());
+    }
+
+    #[test]
+    fn test_keeps_desired_column() {
+        let buffer = buffer(&["long line", "ab", "", "another line"]);
+        let mut cursor = Cursor::new(&buffer, 7, 0, 8);
+
+        cursor.move_by(&buffer, Movement::Down, 8);
+        assert_eq!((cursor.x, cursor.y), (2, 1));
+        cursor.move_by(&buffer, Movement::Down, 8);
+        assert_eq!((cursor.x, cursor.y), (0, 2));
+        cursor.move_by(&buffer, Movement::Down, 8);
+        assert_eq!((cursor.x, cursor.y), (7, 3));
+
+        // Moving along a line sets a new desired column
+        cursor.move_by(&buffer, Movement::Home, 8);
+        cursor.move_by(&buffer, Movement::Up, 8);
+        cursor.move_by(&buffer, Movement::Up, 8);
+        assert_eq!((cursor.x, cursor.y), (0, 1));
+    }
+
+    #[test]
+    fn test_wraps_across_lines() {
+        let buffer = buffer(&["ab", "日本"]);
+        let mut cursor = Cursor::new(&buffer, 2, 0, 8);
+
+        cursor.move_by(&buffer, Movement::Right, 8);
+        assert_eq!((cursor.x, cursor.y), (0, 1));
+        cursor.move_by(&buffer, Movement::Left, 8);
+        assert_eq!((cursor.x, cursor.y), (2, 0));
+
+        cursor.move_by(&buffer, Movement::Down, 8);
+        cursor.move_by(&buffer, Movement::End, 8);
+        cursor.move_by(&buffer, Movement::Right, 8);
+        assert_eq!((cursor.x, cursor.y), (2, 1));
+    }
+
+    #[test]
+    fn test_position_in_display_columns() {
+        let buffer = buffer(&["日本語", "👨\u{200d}👩\u{200d}👧x"]);
+        let mut cursor = Cursor::new(&buffer, 2, 0, 8);
+        assert_eq!(cursor.position(&buffer, 8), Location { x: 4, y: 0 });
+
+        cursor.move_by(&buffer, Movement::Down, 8);
+        assert_eq!((cursor.x, cursor.y), (2, 1));
+        assert_eq!(cursor.position(&buffer, 8), Location { x: 3, y: 1 });
+    }
+
+    #[test]
+    fn test_desired_display_column() {
+        let buffer = buffer(&["日本語", "abcdef", "\tx"]);
+        let mut cursor = Cursor::new(&buffer, 2, 0, 8);
+
+        cursor.move_by(&buffer, Movement::Down, 8);
+        assert_eq!((cursor.x, cursor.y), (4, 1));
+        cursor.move_by(&buffer, Movement::Down, 8);
+        assert_eq!((cursor.x, cursor.y), (0, 2));
+        cursor.move_by(&buffer, Movement::Right, 8);
+        cursor.move_by(&buffer, Movement::Up, 8);
+        assert_eq!((cursor.x, cursor.y), (6, 1));
+    }
+
+    #[test]
+    fn test_move_visually() {
+        let buffer = buffer(&["abcdefghij", "xy"]);
+        let wrap = SoftWrap {
+            enabled: true,
+            at_blanks: false,
+        };
+        let chunks = |content: &Data| wrap.chunks(content, 4, 8);
+        let mut cursor = Cursor::new(&buffer, 1, 0, 8);
+
+        cursor.move_visually(&buffer, Movement::Down, 8, chunks);
+        assert_eq!((cursor.x, cursor.y), (5, 0));
+        cursor.move_visually(&buffer, Movement::Down, 8, chunks);
+        assert_eq!((cursor.x, cursor.y), (9, 0));
+        cursor.move_visually(&buffer, Movement::Down, 8, chunks);
+        assert_eq!((cursor.x, cursor.y), (1, 1));
+        cursor.move_visually(&buffer, Movement::Up, 8, chunks);
+        assert_eq!((cursor.x, cursor.y), (9, 0));
+
+        // The end of a row short of the column keeps the cursor on it
+        let mut cursor = Cursor::new(&buffer, 3, 0, 8);
+        cursor.move_visually(&buffer, Movement::Down, 8, chunks);
+        cursor.move_visually(&buffer, Movement::Down, 8, chunks);
+        assert_eq!((cursor.x, cursor.y), (10, 0));
+        cursor.move_visually(&buffer, Movement::Down, 8, chunks);
+        assert_eq!((cursor.x, cursor.y), (2, 1));
+    }
+
+    #[test]
+    fn test_stays_in_buffer() {
+        let buffer = buffer(&["ab", "cd"]);
+        let mut cursor = Cursor::new(&buffer, 0, 0, 8);
+        cursor.move_by(&buffer, Movement::Up, 8);
+        cursor.move_by(&buffer, Movement::Left, 8);
+        assert_eq!((cursor.x, cursor.y), (0, 0));
+
+        cursor.move_by(&buffer, Movement::Down, 8);
+        cursor.move_by(&buffer, Movement::Down, 8);
+        assert_eq!(cursor.y, 1);
+    }
+}
@@ -1,20 +1,41 @@
-use std::env;
+use std::ops::Range;
 use std::path::PathBuf;
-use std::str::FromStr;
+use std::time::Instant;
+use std::{env, fs};
 
-use crossterm::event::KeyCode;
-use crossterm::style::{Color, Stylize};
-use syntect::easy::HighlightLines;
-use syntect::parsing::SyntaxSet;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::ContentStyle;
 
+use crate::arguments::Arguments;
 use crate::buffer::Buffer;
+use crate::colour::Palette;
 use crate::config::configuration::NanoConfiguration;
-use crate::content::Data;
+use crate::content::{self, Data, Glyph, UnicodeSegmentation};
+use crate::cursor::{Cursor, Movement};
 use crate::error::{NanoError, NanoResult};
-use crate::terminal::{Position, Terminal};
+use crate::gutter::Gutter;
+use crate::highlight;
+use crate::highlight::assets::Assets;
+use crate::highlight::worker::{HighlightWorker, SyntaxChange, POLL_INTERVAL};
+use crate::history::{History, HistoryKind};
+use crate::message::MessageQueue;
+use crate::position_log::PositionLog;
+use crate::prompt::{Prompt, PromptAction};
+use crate::screen::Screen;
+use crate::shortcut::{self, Mode, ShortcutBar};
+use crate::terminal::{
+    Location, Position, Scrolling, Terminal, STATUS_BAR_HEIGHT, TITLE_BAR_HEIGHT,
+};
+use crate::undo::{Edit, EditKind, UndoStack};
+use crate::whitespace::{self, Whitespace};
+use crate::wrap::{self, Chunk, HardWrap, SoftWrap};
 
 pub const NANO_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// The marker at the end of the rows of a soft wrapped line continued on the
+/// next row
+const WRAP_MARKER: &str = "\\";
+
 /// The Nano editor
 ///
 /// This is the main editor struct. It contains the terminal view, the file
@@ -26,7 +47,40 @@ pub struct NanoEditor {
     /// The terminal view
     terminal: Terminal,
     buffer: Buffer,
-    config: NanoConfiguration,
+    /// The cursor in the buffer, shown in the view through `terminal.cursor`
+    cursor: Cursor,
+    /// The syntax highlighter of the buffer, running on its own thread
+    highlighter: HighlightWorker,
+    /// The colours of the interface, and those the terminal can show
+    palette: Palette,
+    /// The line numbers left of the text, toggled with Alt+N
+    gutter: Gutter,
+    /// How the view follows the cursor, smooth scrolling toggled with Alt+J
+    scrolling: Scrolling,
+    /// The number of columns between tab stops
+    tabsize: usize,
+    /// How tabs and spaces are shown, toggled with Alt+P
+    whitespace: Whitespace,
+    /// Whether long lines are soft wrapped, toggled with Alt+S
+    softwrap: SoftWrap,
+    /// Whether lines typed past the fill column are broken, toggled with
+    /// Alt+L
+    hard_wrap: HardWrap,
+    /// The edits that can be undone with Alt+U and redone with Alt+E
+    undo: UndoStack,
+    /// The frame drawn on the terminal
+    screen: Screen,
+    /// The prompt shown beneath the edit area, if any
+    prompt: Option<Prompt>,
+    /// The history of the prompts, kept between sessions
+    history: History,
+    /// The last cursor position in each file, kept between sessions
+    positions: PositionLog,
+    /// The messages waiting to be shown in the status bar
+    messages: MessageQueue,
+    /// The line and graphemes of the match of the last search, highlighted
+    /// until the next key
+    found: Option<(usize, Range<usize>)>,
 }
 
 impl NanoEditor {
@@ -45,17 +99,75 @@ impl NanoEditor {
     /// ```
     ///
     pub fn new(config: NanoConfiguration) -> NanoResult<Self> {
-        let args = env::args().collect::<Vec<String>>();
-        let file_name =
-            PathBuf::from_str(&args[1]).map_err(|e| NanoError::FileError(e.to_string()))?;
-        let file = Buffer::from_file(file_name)?;
+        let arguments = Arguments::parse(env::args().skip(1))?;
+        let file = Buffer::from_file(&arguments.file)?;
+        let mut messages = MessageQueue::default();
+        let (assets, errors) = Assets::load(
+            config.syntax_dir().as_deref(),
+            config.theme_dir().as_deref(),
+            config.nanorc_dir().as_deref(),
+            NanoConfiguration::cache_dir().as_deref(),
+        );
+        errors.iter().for_each(|e| messages.error(e.to_string()));
+
+        let theme = config.load_theme(&assets.theme_set)?;
+        let palette = config.palette(&theme);
+        let mut highlighter =
+            HighlightWorker::spawn(assets.syntax_set, assets.nanorc, theme, file.clone());
+
+        // The outcome is reported once the worker answers
+        let engine = config.engine(file.file_type());
+        match &arguments.syntax {
+            Some(syntax) => highlighter.set_syntax(syntax, Some(engine)),
+            None => highlighter.detect_syntax(engine),
+        }
         let terminal_view = Terminal::new()?;
 
-        Ok(Self {
+        let screen = Screen::new(terminal_view.width, terminal_view.rows);
+        let mut editor = Self {
+            screen,
             terminal: terminal_view,
             buffer: file,
-            config,
-        })
+            cursor: Cursor::default(),
+            highlighter,
+            palette,
+            gutter: config.gutter(),
+            scrolling: config.scrolling(),
+            tabsize: config.tabsize(),
+            whitespace: config.whitespace(),
+            softwrap: SoftWrap {
+                enabled: arguments.softwrap || config.softwrap().enabled,
+                ..config.softwrap()
+            },
+            hard_wrap: HardWrap {
+                enabled: arguments.break_long_lines || config.hard_wrap().enabled,
+                ..config.hard_wrap()
+            },
+            undo: UndoStack::default(),
+            prompt: None,
+            history: History::load(),
+            positions: PositionLog::load(),
+            messages,
+            found: None,
+        };
+
+        if let Some((line, column)) = editor
+            .canonical_path()
+            .and_then(|path| editor.positions.position(path))
+        {
+            editor.goto_position(line, column);
+        }
+
+        Ok(editor)
+    }
+
+    /// The absolute path of the file being edited, used to remember the
+    /// cursor position between sessions
+    fn canonical_path(&self) -> Option<PathBuf> {
+        self.buffer
+            .name
+            .as_ref()
+            .and_then(|name| fs::canonicalize(name).ok())
     }
 
     /// The main loop of the editor
@@ -65,7 +177,7 @@ impl NanoEditor {
     /// # Errors
     /// This function will return an error if the editor cannot be rendered.
     pub fn run(&mut self) -> NanoResult<()> {
-        Terminal::set_title(&format!(
+        Terminal::set_title(format!(
             "Nano - {}",
             self.buffer
                 .name
@@ -75,174 +187,826 @@ impl NanoEditor {
 
         loop {
             if let Err(e) = self.render() {
-                NanoEditor::handle_error(e)?;
+                self.handle_error(e)?;
             }
 
-            if let Err(e) = self.process_key() {
-                NanoEditor::handle_error(e)?;
+            if let Err(e) = self.process_event() {
+                self.handle_error(e)?;
             }
         }
     }
 
+    /// Draw the title bar
+    /// The title is centred in a bar across the whole width of the terminal.
     pub fn draw_status_bar(&mut self) -> NanoResult<()> {
         let status_bar_message = format!(
-            "Nano {} - File: {} Modified", // TODO::Change Modified to showcase if the file is dirty or not.
+            "Nano {} - File: {}{}",
             NANO_VERSION,
             self.buffer
                 .name
                 .as_ref()
                 .unwrap_or(&String::from("Untitled")),
-        )
-        .with(Color::Black)
-        .on(Color::White)
-        .to_string();
-
-        // Get the terminal width and the text length
-        let terminal_width = self.terminal.width;
-        let text_length = status_bar_message.len();
+            if self.buffer.modified {
+                " Modified"
+            } else {
+                ""
+            },
+        );
 
         // Calculate the number of spaces to add on each side of the text
-        let num_spaces = (terminal_width as usize - text_length) / 2;
+        let width = self.terminal.width as usize;
+        let (text, text_width) = content::fit(&status_bar_message, width);
+        let num_spaces = (width - text_width) / 2;
         let centered_text = format!(
-            "{:>width$}",
-            status_bar_message,
-            width = text_length + num_spaces
+            "{}{}{}",
+            " ".repeat(num_spaces),
+            text,
+            " ".repeat(width - num_spaces - text_width)
         );
 
-        Terminal::write(centered_text);
+        let title = self.palette.paint(self.palette.title, &centered_text);
+        self.screen.print(0, 0, &[title]);
 
         Ok(())
     }
 
-    /// Process the key event captured from the terminal
-    pub fn process_key(&mut self) -> NanoResult<()> {
-        let event = self.terminal.read_key()?;
+    /// Draw the prompt
+    /// This will draw the active prompt in the row beneath the edit area, or
+    /// the current message when there is no prompt.
+    pub fn draw_prompt(&mut self) -> NanoResult<()> {
+        let y = TITLE_BAR_HEIGHT + self.terminal.height;
+
+        if let Some(prompt) = &self.prompt {
+            let (line, _) = prompt.render(self.terminal.width, &self.palette);
+            self.screen.print(0, y, &[line]);
+        } else if let Some(message) = self.messages.current() {
+            let line = message.render(self.terminal.width, &self.palette);
+            self.screen.print(0, y, &line);
+        }
 
-        match event.code {
-            KeyCode::Char('q') => NanoEditor::exit()?,
-            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down => {
-                self.navigate_cursor(event.code)
-            }
-            _ => {}
+        Ok(())
+    }
+
+    /// Draw the shortcut bar
+    /// This will draw the shortcuts of the current mode in the two rows
+    /// beneath the prompt.
+    pub fn draw_shortcut_bar(&mut self) -> NanoResult<()> {
+        let bar = ShortcutBar::new(self.mode().shortcuts(), self.terminal.width);
+        let top = TITLE_BAR_HEIGHT + self.terminal.height + STATUS_BAR_HEIGHT;
+
+        for (index, row) in (top..top + 2).enumerate() {
+            self.screen
+                .print(0, row, &bar.render_row(index, &self.palette));
         }
 
         Ok(())
     }
 
-    fn navigate_cursor(&mut self, event: KeyCode) {
-        let Position { mut x, mut y } = self.terminal.cursor;
-        let document_height = self.buffer.len() as u16;
-        let document_width = self
-            .buffer
-            .row(y as usize)
-            .map_or(0, |content| content.len()) as u16;
+    /// The current mode, which decides the shortcuts shown
+    fn mode(&self) -> Mode {
+        self.prompt
+            .as_ref()
+            .map_or(Mode::Edit, |prompt| prompt.mode)
+    }
 
-        if x > document_width {
-            x = document_width
+    /// Process the event captured from the terminal
+    /// Keys and pastes go to the prompt while one is shown. Any of them
+    /// clears the current message, which otherwise clears itself after a
+    /// timeout.
+    pub fn process_event(&mut self) -> NanoResult<()> {
+        // Redraw as lines are highlighted until the screen is complete
+        let timeout = match self.messages.time_left(Instant::now()) {
+            Some(left) if self.highlighter.is_pending() => Some(left.min(POLL_INTERVAL)),
+            None if self.highlighter.is_pending() => Some(POLL_INTERVAL),
+            left => left,
+        };
+        let Some(event) = self.terminal.read_event(timeout)? else {
+            self.messages.expire(Instant::now());
+            return Ok(());
+        };
+        if let Event::Resize(width, rows) = event {
+            self.resize(width, rows);
+            return Ok(());
         }
+        self.messages.dismiss();
 
         match event {
-            KeyCode::Down => {
-                if y > document_height {
-                    y = document_height
+            Event::Key(event) if self.prompt.is_some() => self.process_prompt_key(event),
+            Event::Key(event) => self.process_key(event),
+            Event::Paste(text) => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.paste(&text);
                 }
-                y = y.saturating_add(1)
+                Ok(())
             }
-            KeyCode::Up => y = y.saturating_sub(1),
-            KeyCode::Left => x = x.saturating_sub(1),
-            KeyCode::Right => x = x.saturating_add(1),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resize the editor to a terminal of the given size
+    fn resize(&mut self, width: u16, rows: u16) {
+        self.terminal.resize(width, rows);
+        self.screen.resize(width, rows);
+    }
+
+    /// Process a key event while editing the buffer
+    fn process_key(&mut self, event: KeyEvent) -> NanoResult<()> {
+        let control = event.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = event.modifiers.contains(KeyModifiers::ALT);
+        self.found = None;
+
+        match event.code {
+            KeyCode::Char('x') if control => self.exit()?,
+            KeyCode::Char('w') if control => self.open_search(),
+            // Terminals report Ctrl+/ as Ctrl+_ or Ctrl+7
+            KeyCode::Char('/' | '_' | '7') if control => self.open_goto_line(),
+            KeyCode::Char('g') if alt => self.open_goto_line(),
+            KeyCode::Char('y') if alt => self.open_syntax(),
+            KeyCode::Char('n') if alt => self.gutter.shown = !self.gutter.shown,
+            KeyCode::Char('j') if alt => self.toggle_smooth_scrolling(),
+            KeyCode::Char('p') if alt => self.toggle_whitespace(),
+            KeyCode::Char('s') if alt => self.toggle_softwrap(),
+            KeyCode::Char('l') if alt => self.toggle_hard_wrap(),
+            KeyCode::Char('u') if alt => self.undo(),
+            KeyCode::Char('e') if alt => self.redo(),
+            KeyCode::Left => self.move_cursor(Movement::Left),
+            KeyCode::Right => self.move_cursor(Movement::Right),
+            KeyCode::Up => self.move_cursor(Movement::Up),
+            KeyCode::Down => self.move_cursor(Movement::Down),
+            KeyCode::Home => self.move_cursor(Movement::Home),
+            KeyCode::End => self.move_cursor(Movement::End),
+            KeyCode::Char('a') if control => self.move_cursor(Movement::Home),
+            KeyCode::Char('e') if control => self.move_cursor(Movement::End),
+            KeyCode::Char(c) if !control && !alt => self.insert(c.encode_utf8(&mut [0; 4])),
+            KeyCode::Char(c) => self.report_unavailable(c, control),
+            KeyCode::Tab => self.insert("\t"),
+            KeyCode::Enter => self.break_line(),
+            KeyCode::Backspace => self.delete_backward(),
+            KeyCode::Delete => self.delete_forward(),
             _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Tell that a shortcut of the bar does nothing yet
+    /// The bar lists nano's shortcuts, not all of which are implemented.
+    fn report_unavailable(&mut self, c: char, control: bool) {
+        let key = shortcut::notation(c, control);
+        if let Some(found) = Mode::Edit.shortcuts().iter().find(|s| s.key == key) {
+            self.messages
+                .warning(format!("{} is not implemented yet", found.label));
+        }
+    }
+
+    /// Switch between scrolling line by line and by half a screen
+    fn toggle_smooth_scrolling(&mut self) {
+        self.scrolling.smooth = !self.scrolling.smooth;
+        let state = if self.scrolling.smooth {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        self.messages.info(format!("Smooth scrolling {}", state));
+    }
+
+    /// Switch between showing tabs and spaces and leaving them blank
+    fn toggle_whitespace(&mut self) {
+        self.whitespace.shown = !self.whitespace.shown;
+        let state = if self.whitespace.shown {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        self.messages.info(format!("Whitespace display {}", state));
+    }
+
+    /// Switch between soft wrapping long lines and scrolling them sideways
+    fn toggle_softwrap(&mut self) {
+        self.softwrap.enabled = !self.softwrap.enabled;
+        self.terminal.offset_chunk = 0;
+        let state = if self.softwrap.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        self.messages
+            .info(format!("Soft wrapping of overlong lines {}", state));
+    }
+
+    /// Switch between breaking lines typed past the fill column and not
+    fn toggle_hard_wrap(&mut self) {
+        self.hard_wrap.enabled = !self.hard_wrap.enabled;
+        let state = if self.hard_wrap.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        self.messages
+            .info(format!("Hard-wrapping of overlong lines {}", state));
+    }
+
+    /// Get the line of the cursor, and the number of lines it takes in the
+    /// buffer
+    /// An empty buffer has an empty line, which is not in the buffer yet.
+    fn cursor_line(&self) -> (String, usize) {
+        match self.buffer.row(self.cursor.y) {
+            Some(content) => (content.text.to_string(), 1),
+            None => (String::new(), 0),
+        }
+    }
+
+    /// Insert text at the cursor
+    /// A line typed past the fill column is broken in the same edit, so that
+    /// both are undone together.
+    fn insert(&mut self, text: &str) {
+        let Cursor { x, y, .. } = self.cursor;
+        let (line, removed) = self.cursor_line();
+        let offset = Data::from(line.as_str()).byte_offset(x);
+        let typed = format!("{}{}", &line[..offset], text);
+        let x = Data::from(typed.as_str()).grapheme_count();
+        let line = format!("{}{}", typed, &line[offset..]);
+
+        let (inserted, after) = match self.hard_wrap.wrap(&line, x, self.tabsize) {
+            Some((lines, (column, row))) => (lines, (column, y + row)),
+            None => (vec![line], (x, y)),
+        };
+        self.edit(EditKind::Typing, y, removed, inserted, after);
+    }
+
+    /// Delete the grapheme before the cursor
+    /// At the start of a line, the line is joined to the one above it.
+    fn delete_backward(&mut self) {
+        let Cursor { x, y, .. } = self.cursor;
+        let (line, removed) = self.cursor_line();
+        if x > 0 {
+            let data = Data::from(line.as_str());
+            let (start, end) = (data.byte_offset(x - 1), data.byte_offset(x));
+            let line = format!("{}{}", &line[..start], &line[end..]);
+            self.edit(EditKind::Backspace, y, removed, vec![line], (x - 1, y));
+        } else if let Some(above) = y.checked_sub(1).and_then(|row| self.buffer.row(row)) {
+            let x = above.grapheme_count();
+            let line = format!("{}{}", above.text, line);
+            self.edit(EditKind::Lines, y - 1, removed + 1, vec![line], (x, y - 1));
+        }
+    }
+
+    /// Delete the grapheme under the cursor
+    /// At the end of a line, the line below it is joined to it.
+    fn delete_forward(&mut self) {
+        let Cursor { x, y, .. } = self.cursor;
+        let (line, removed) = self.cursor_line();
+        let data = Data::from(line.as_str());
+        if x < data.grapheme_count() {
+            let (start, end) = (data.byte_offset(x), data.byte_offset(x + 1));
+            let line = format!("{}{}", &line[..start], &line[end..]);
+            self.edit(EditKind::Delete, y, removed, vec![line], (x, y));
+        } else if let Some(below) = self.buffer.row(y + 1) {
+            let line = format!("{}{}", line, below.text);
+            self.edit(EditKind::Lines, y, removed + 1, vec![line], (x, y));
+        }
+    }
+
+    /// Break the line at the cursor, moving the cursor to the new line
+    fn break_line(&mut self) {
+        let Cursor { x, y, .. } = self.cursor;
+        let (line, removed) = self.cursor_line();
+        let offset = Data::from(line.as_str()).byte_offset(x);
+        let inserted = vec![line[..offset].to_string(), line[offset..].to_string()];
+        self.edit(EditKind::Lines, y, removed, inserted, (0, y + 1));
+    }
+
+    /// Replace `removed` lines from `row`, recording the edit to undo it
+    /// `after` is where the cursor moves to, as `(column, line)`.
+    fn edit(
+        &mut self,
+        kind: EditKind,
+        row: usize,
+        removed: usize,
+        inserted: Vec<String>,
+        after: (usize, usize),
+    ) {
+        let Cursor { x, y, .. } = self.cursor;
+        let edit = Edit {
+            kind,
+            row,
+            removed: self.buffer.data[row..row + removed]
+                .iter()
+                .map(|content| content.text.to_string())
+                .collect(),
+            inserted,
+            before: (x, y),
+            after,
+        };
+        self.undo.push(edit.clone());
+        self.apply(&edit);
+    }
+
+    /// Undo the last edit
+    fn undo(&mut self) {
+        match self.undo.undo() {
+            Some(edit) => self.apply(&edit),
+            None => self.messages.warning("Nothing to undo"),
+        }
+    }
+
+    /// Redo the last edit undone
+    fn redo(&mut self) {
+        match self.undo.redo() {
+            Some(edit) => self.apply(&edit),
+            None => self.messages.warning("Nothing to redo"),
+        }
+    }
+
+    /// Make an edit to the buffer and its highlighting, and move the cursor
+    /// to where it leaves it
+    /// The edit is already on the undo stack, which tells whether the buffer
+    /// is back to the file as it was read.
+    fn apply(&mut self, edit: &Edit) {
+        let removed = edit.removed.len();
+        self.highlighter
+            .edit(edit.row, removed, edit.inserted.clone());
+        self.buffer
+            .replace_lines(edit.row, removed, edit.inserted.clone());
+        self.buffer.modified = !self.undo.is_saved();
+
+        let (x, y) = edit.after;
+        self.cursor = Cursor::new(&self.buffer, x, y, self.tabsize);
+        self.terminal.cursor = self.cursor.position(&self.buffer, self.tabsize);
+    }
+
+    /// Open the go to line prompt
+    fn open_goto_line(&mut self) {
+        self.prompt = Some(Prompt::new(
+            Mode::GotoLine,
+            "Enter line number, column number",
+        ));
+    }
+
+    /// Open the search prompt
+    /// The last search is offered as the default, like in nano.
+    fn open_search(&mut self) {
+        let history = self.history.entries(HistoryKind::Search);
+        let message = match history.last() {
+            Some(last) => format!("Search [{}]", last),
+            None => String::from("Search"),
+        };
+        self.prompt = Some(Prompt::new(Mode::Search, message).with_history(history));
+    }
+
+    /// Open the prompt to choose the syntax of the buffer
+    fn open_syntax(&mut self) {
+        let message = format!("Syntax [{}]", self.highlighter.syntax_name());
+        self.prompt = Some(Prompt::new(Mode::Syntax, message));
+    }
+
+    /// Process a key event while a prompt is shown
+    fn process_prompt_key(&mut self, event: KeyEvent) -> NanoResult<()> {
+        let Some(prompt) = &mut self.prompt else {
+            return Ok(());
+        };
+        let mode = prompt.mode;
+        let control = event.modifiers.contains(KeyModifiers::CONTROL);
+
+        match prompt.handle_key(event) {
+            PromptAction::Continue => {}
+            PromptAction::Cancel => self.prompt = None,
+            PromptAction::Submit(input) => {
+                self.prompt = None;
+                match mode {
+                    Mode::Search => self.search(&input),
+                    Mode::GotoLine => self.goto_line(&input),
+                    Mode::Syntax => self.set_syntax(&input),
+                    Mode::Edit => {}
+                }
+            }
+            PromptAction::Unhandled(event) => match (mode, event.code) {
+                (Mode::Search | Mode::GotoLine, KeyCode::Char('y')) if control => {
+                    self.prompt = None;
+                    self.goto_position(1, 1);
+                }
+                (Mode::Search | Mode::GotoLine, KeyCode::Char('v')) if control => {
+                    self.prompt = None;
+                    self.goto_position(self.buffer.len(), 1);
+                }
+                (Mode::Search, KeyCode::Char('t')) if control => self.open_goto_line(),
+                _ => {}
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Search for the string typed in the search prompt
+    /// An empty string repeats the last search.
+    fn search(&mut self, input: &str) {
+        if !input.is_empty() {
+            self.history.add(HistoryKind::Search, input);
+        }
+        let Some(needle) = self.history.entries(HistoryKind::Search).last() else {
+            return;
         };
 
-        self.terminal.set_cursor_position((x, y).into())
+        match self.buffer.find(needle, self.cursor.y, self.cursor.x) {
+            Some(((row, column), wrapped)) => {
+                if wrapped {
+                    self.messages.info("Search Wrapped");
+                }
+                let length = needle.graphemes(true).count();
+                self.goto_position(row + 1, column + 1);
+                self.found = Some((row, column..column + length));
+            }
+            None => self.messages.warning(format!("\"{}\" not found", needle)),
+        }
+    }
+
+    /// Use the syntax typed in the syntax prompt, by name or extension
+    fn set_syntax(&mut self, name: &str) {
+        if name.is_empty() {
+            return;
+        }
+
+        self.highlighter.set_syntax(name, None);
+    }
+
+    /// Report the syntax changes answered by the highlight worker
+    fn report_syntax_changes(&mut self) {
+        for change in self.highlighter.syntax_changes() {
+            match change {
+                SyntaxChange::Set(name) => self.messages.info(format!("Syntax set to {}", name)),
+                SyntaxChange::Unknown(name) => self
+                    .messages
+                    .error(format!("Unknown syntax name: {}", name)),
+                SyntaxChange::Detected(_) => {}
+                SyntaxChange::NotDetected => self.messages.warning(format!(
+                    "Syntax not found for {}, using plain text",
+                    self.buffer.name.as_deref().unwrap_or("Untitled")
+                )),
+            }
+        }
+    }
+
+    /// Go to the `line[,column]` typed in the go to line prompt
+    /// Negative numbers count from the end of the file or line, like in nano.
+    fn goto_line(&mut self, input: &str) {
+        let mut parts = input.split([',', ' ']).filter(|part| !part.is_empty());
+        let line = parts.next().map(str::parse::<isize>);
+        let column = parts.next().map(str::parse::<isize>).unwrap_or(Ok(1));
+
+        let (Some(Ok(line)), Ok(column)) = (line, column) else {
+            self.messages.error("Invalid line or column number");
+            return;
+        };
+
+        let line = from_end(line, self.buffer.len());
+        let length = self
+            .buffer
+            .row(line.saturating_sub(1))
+            .map_or(0, |content| content.grapheme_count());
+        let column = from_end(column, length + 1);
+
+        self.goto_position(line, column);
+    }
+
+    /// Move the cursor to a one-based line and column, clamped to the buffer
+    fn goto_position(&mut self, line: usize, column: usize) {
+        let x = column.saturating_sub(1);
+        let y = line.saturating_sub(1);
+        self.cursor = Cursor::new(&self.buffer, x, y, self.tabsize);
+        self.terminal.cursor = self.cursor.position(&self.buffer, self.tabsize);
+    }
+
+    /// Move the cursor a step through the buffer, following it with the view
+    /// Up and down move by rows of the screen when lines are soft wrapped.
+    fn move_cursor(&mut self, movement: Movement) {
+        match movement {
+            Movement::Up | Movement::Down if self.softwrap.enabled => {
+                let (softwrap, width, tabsize) = (self.softwrap, self.wrap_width(), self.tabsize);
+                self.cursor
+                    .move_visually(&self.buffer, movement, tabsize, |content| {
+                        softwrap.chunks(content, width, tabsize)
+                    });
+            }
+            _ => self.cursor.move_by(&self.buffer, movement, self.tabsize),
+        }
+        self.terminal.cursor = self.cursor.position(&self.buffer, self.tabsize);
     }
 
     /// Render the editor
     /// This will render the editor, including the file, cursor, and status bar.
-    ///
+    /// The frame is drawn into the screen, which writes what changed since
+    /// the last one to the terminal.
     fn render(&mut self) -> NanoResult<()> {
-        Terminal::hide_cursor()?;
+        self.screen.clear();
+        if self.terminal.is_too_small() {
+            self.screen
+                .print_str(0, 0, "Terminal too small", ContentStyle::default());
+            return self.screen.flush();
+        }
 
+        if self.softwrap.enabled {
+            let chunk = wrap::chunk_of(&self.chunks(self.cursor.y), self.cursor.x);
+            let (buffer, softwrap) = (&self.buffer, self.softwrap);
+            let (width, tabsize) = (self.wrap_width(), self.tabsize);
+            self.terminal
+                .scroll_to_chunk(chunk, buffer.len(), self.scrolling, |line| {
+                    buffer
+                        .row(line)
+                        .map_or(1, |content| softwrap.chunks(content, width, tabsize).len())
+                });
+        } else {
+            self.terminal
+                .scroll_to_cursor(self.buffer.len(), self.text_width(), self.scrolling);
+        }
         self.draw_status_bar()?;
         self.render_contents()?;
+        self.draw_prompt()?;
+        self.draw_shortcut_bar()?;
+
+        // The cursor is placed with the chunk it is in when soft wrapping
+        if let Some(prompt) = &self.prompt {
+            let (_, x) = prompt.render(self.terminal.width, &self.palette);
+            let y = TITLE_BAR_HEIGHT + self.terminal.height;
+            self.screen.set_cursor((x, y).into());
+        } else if !self.softwrap.enabled {
+            // The view was scrolled to the cursor, so it is on the screen
+            let Location { x, y } = self.terminal.cursor;
+            self.screen.set_cursor(Position {
+                x: (x.saturating_sub(self.terminal.offset.x) + self.gutter_width()) as u16,
+                y: y.saturating_sub(self.terminal.offset.y) as u16 + TITLE_BAR_HEIGHT,
+            });
+        }
 
-        self.terminal.set_cursor_position(Position {
-            x: self
-                .terminal
-                .cursor
-                .x
-                .saturating_sub(self.terminal.offset.x),
-            y: self
-                .terminal
-                .cursor
-                .y
-                .saturating_sub(self.terminal.offset.y),
-        });
-
-        Terminal::show_cursor()?;
-        Terminal::flush()?;
-        Ok(())
+        self.screen.flush()
     }
 
     fn render_contents(&mut self) -> NanoResult<()> {
         let height = self.terminal.height;
+        let first = self.terminal.offset.y;
+        self.highlighter.highlight_to(first + height as usize);
+        if let Err(e) = self.highlighter.poll() {
+            self.messages.error(format!("Failed to highlight: {}", e));
+        }
+        self.report_syntax_changes();
+        let gutter_width = self.gutter_width();
+        if self.softwrap.enabled {
+            self.render_wrapped(gutter_width);
+            return Ok(());
+        }
 
         for terminal_row in 0..height {
-            Terminal::clear_current_line()?;
-
-            if let Some(content) = self
-                .buffer
-                .row(terminal_row as usize + self.terminal.offset.y as usize)
-            {
-                self.render_content(content, terminal_row)?
+            let row = first + terminal_row as usize;
+            let y = TITLE_BAR_HEIGHT + terminal_row;
+            if row < self.buffer.len() {
+                self.render_content(row, y, gutter_width);
             } else {
-                Terminal::write("~\r");
+                self.screen.print_str(0, y, "~", ContentStyle::default());
             }
         }
 
         Ok(())
     }
 
-    fn render_content(&self, content: &Data, _line_number: u16) -> NanoResult<()> {
-        let width = self.terminal.width as usize;
-        let start = self.terminal.offset.x as usize;
-        let end = self.terminal.offset.x as usize + width;
-        let text = &content.display_range(start, end);
+    /// Render the lines of the buffer soft wrapped, from the chunk at the top
+    /// of the view
+    fn render_wrapped(&mut self, gutter_width: usize) {
+        let mut line = self.terminal.offset.y;
+        let mut first = self.terminal.offset_chunk;
+        let mut terminal_row = 0;
+        while terminal_row < self.terminal.height {
+            if line >= self.buffer.len() {
+                let y = TITLE_BAR_HEIGHT + terminal_row;
+                self.screen.print_str(0, y, "~", ContentStyle::default());
+                terminal_row += 1;
+                continue;
+            }
 
-        let ss = SyntaxSet::load_defaults_newlines();
-        let syntax =
-            ss.find_syntax_by_extension(self.buffer.file_type())
-                .ok_or(NanoError::Generic(format!(
-                    "Syntax not found for {}",
-                    self.buffer.file_type()
-                )))?;
+            let chunks = self.chunks(line);
+            for chunk in first..chunks.len() {
+                if terminal_row == self.terminal.height {
+                    break;
+                }
+                let y = TITLE_BAR_HEIGHT + terminal_row;
+                self.render_chunk(line, y, gutter_width, &chunks, chunk);
+                terminal_row += 1;
+            }
+            line += 1;
+            first = 0;
+        }
+    }
 
-        let theme = &self.config.load_themes().expect("failed to load theme");
+    /// Render a chunk of a soft wrapped line in a row of the screen
+    /// Only the first chunk of a line is numbered, and every chunk but the
+    /// last ends with a marker in the column left for it.
+    fn render_chunk(
+        &mut self,
+        line_number: usize,
+        y: u16,
+        gutter_width: usize,
+        chunks: &[Chunk],
+        chunk: usize,
+    ) {
+        let gutter = if chunk == 0 {
+            let cursor_row = self.terminal.cursor.y;
+            self.gutter
+                .render(line_number, cursor_row, gutter_width, &self.palette)
+        } else {
+            self.gutter.blank(gutter_width, &self.palette)
+        };
+        let left = self.screen.print(0, y, &[gutter]);
+
+        let Chunk { start, column } = chunks[chunk];
+        let end = chunks.get(chunk + 1).map_or(usize::MAX, |next| next.start);
+        self.draw_text(line_number, y, left, start..end, column);
+        if chunk + 1 < chunks.len() {
+            let right = left + self.wrap_width() as u16;
+            self.screen
+                .print_str(right, y, WRAP_MARKER, ContentStyle::default());
+        }
 
-        let mut h = HighlightLines::new(syntax, theme);
+        if line_number == self.cursor.y && chunk == wrap::chunk_of(chunks, self.cursor.x) {
+            let x = left + (self.terminal.cursor.x - column) as u16;
+            self.screen.set_cursor((x, y).into());
+        }
+    }
 
-        let ranges: Vec<(syntect::highlighting::Style, &str)> = h.highlight_line(text, &ss)?;
+    /// Render a line of the buffer in a row of the screen
+    fn render_content(&mut self, line_number: usize, y: u16, gutter_width: usize) {
+        let Some(content) = self.buffer.row(line_number) else {
+            return;
+        };
+        let gutter = self.gutter.render(
+            line_number,
+            self.terminal.cursor.y,
+            gutter_width,
+            &self.palette,
+        );
+        // Only the line of the cursor is scrolled horizontally, by display
+        // columns, a wide grapheme cut by the left edge being left out
+        let width = self.text_width() as usize;
+        let scrolled = if line_number == self.terminal.cursor.y {
+            self.terminal.offset.x
+        } else {
+            0
+        };
+        let tabsize = self.tabsize;
+        let start = content.grapheme_at_column(scrolled, tabsize);
+        let start = if content.column(start, tabsize) < scrolled {
+            start + 1
+        } else {
+            start
+        };
+        let end = content.grapheme_at_column(scrolled + width, tabsize) + 1;
+        let longer = content.width(tabsize) > scrolled + width;
 
-        let result = syntect::util::as_24_bit_terminal_escaped(&ranges[..], false);
+        let left = self.screen.print(0, y, &[gutter]);
+        self.draw_text(line_number, y, left, start..end, scrolled);
 
-        Terminal::write(result);
+        // Mark the lines that go on beyond the edges of the screen
+        if scrolled > 0 {
+            self.screen.print_str(left, y, "<", ContentStyle::default());
+        }
+        if longer {
+            let right = left + width as u16 - 1;
+            self.screen
+                .print_str(right, y, ">", ContentStyle::default());
+        }
+    }
 
-        Ok(())
+    /// Draw the graphemes `graphemes` of a line from column `left` of a row
+    /// of the screen, `left` showing display column `scrolled` of the line
+    fn draw_text(
+        &mut self,
+        line_number: usize,
+        y: u16,
+        left: u16,
+        graphemes: Range<usize>,
+        scrolled: usize,
+    ) {
+        let Some(content) = self.buffer.row(line_number) else {
+            return;
+        };
+        let (start, end) = (graphemes.start, graphemes.end);
+        let tabsize = self.tabsize;
+
+        // Lines are drawn unhighlighted until the worker gets to them
+        let default = self.highlighter.default_style();
+        let unhighlighted = [(default, 0..content.text.len())];
+        let spans = self
+            .highlighter
+            .spans(line_number)
+            .unwrap_or(&unhighlighted);
+        let ranges = highlight::clip(&content.text, spans, start, end);
+
+        let mut column = content.column(start, tabsize);
+        let mut x = left + (column - scrolled) as u16;
+        let control = self.palette.depth.style(self.palette.control);
+        // Trailing whitespace is drawn over the syntax highlighting
+        let warning = self.palette.depth.style(self.palette.warning);
+        let search = self.palette.depth.style(self.palette.search);
+        let found = match &self.found {
+            Some((row, found)) if *row == line_number => found.clone(),
+            _ => 0..0,
+        };
+        let trailing = match self.whitespace.trailing {
+            true => whitespace::trailing_start(&content.text),
+            false => usize::MAX,
+        };
+        let mut index = start;
+        for (style, text) in ranges {
+            let style = self.palette.depth.syntax_style(style, default);
+            for grapheme in text.graphemes(true) {
+                let style = if found.contains(&index) {
+                    search
+                } else if index >= trailing {
+                    warning
+                } else {
+                    style
+                };
+                let glyph = Glyph::new(grapheme, column, tabsize);
+                column += glyph.width();
+                index += 1;
+                x = match glyph {
+                    Glyph::Text(" ") => self.screen.print_str(x, y, self.whitespace.space(), style),
+                    Glyph::Text(text) => self.screen.print_str(x, y, text, style),
+                    Glyph::Tab(width) => {
+                        self.screen
+                            .print_str(x, y, &self.whitespace.tab(width), style)
+                    }
+                    Glyph::Escaped(text) => self.screen.print_str(x, y, &text, control),
+                };
+            }
+        }
+    }
+
+    /// Get the chunks a line is soft wrapped into
+    fn chunks(&self, line: usize) -> Vec<Chunk> {
+        self.buffer.row(line).map_or_else(
+            || vec![Chunk::default()],
+            |content| {
+                self.softwrap
+                    .chunks(content, self.wrap_width(), self.tabsize)
+            },
+        )
+    }
+
+    /// The number of columns soft wrapped text is shown in, the last column
+    /// being left for the marker of a continued line
+    fn wrap_width(&self) -> usize {
+        (self.text_width() as usize).saturating_sub(1).max(1)
+    }
+
+    /// The width of the line number gutter
+    /// The gutter is left out when it would not leave room for the text.
+    fn gutter_width(&self) -> usize {
+        let width = self.gutter.width(self.buffer.len());
+        if width * 2 > self.terminal.width as usize {
+            0
+        } else {
+            width
+        }
+    }
+
+    /// The number of columns the text is shown in, beside the gutter
+    fn text_width(&self) -> u16 {
+        self.terminal.width - self.gutter_width() as u16
     }
 
     /// Handle error
-    fn handle_error(e: NanoError) -> NanoResult<()> {
+    fn handle_error(&mut self, e: NanoError) -> NanoResult<()> {
         log::error!("{}", e);
-        NanoEditor::exit()?;
+        self.exit()?;
 
         Ok(())
     }
 
     /// Exit terminal
-    fn exit() -> NanoResult<()> {
+    /// This will save the state kept between sessions before exiting.
+    fn exit(&mut self) -> NanoResult<()> {
+        if let Err(e) = self.history.save() {
+            log::error!("Failed to save history: {}", e);
+        }
+
+        if let Some(path) = self.canonical_path() {
+            let Cursor { x, y, .. } = self.cursor;
+            self.positions.record(path, y + 1, x + 1);
+        }
+        if let Err(e) = self.positions.save() {
+            log::error!("Failed to save cursor positions: {}", e);
+        }
+
         Terminal::reset()?;
         Terminal::clear()?;
         Terminal::flush()?;
         std::process::exit(0);
     }
 }
+
+/// Resolve a one-based position that counts from the end when negative
+fn from_end(position: isize, length: usize) -> usize {
+    if position < 0 {
+        (length as isize + position + 1).max(1) as usize
+    } else {
+        position as usize
+    }
+}
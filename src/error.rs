@@ -8,9 +8,15 @@ pub enum NanoError {
     #[error("File error: {0}")]
     FileError(String),
 
+    #[error("Config error: {0}")]
+    Config(#[from] config::ConfigError),
+
     #[error("syntect error: {0}")]
     Syntect(#[from] syntect::Error),
 
+    #[error("Failed to load {}: {}", .0.display(), .1)]
+    Asset(std::path::PathBuf, String),
+
     #[error("generic error: {0}")]
     Generic(String),
 }
@@ -0,0 +1,101 @@
+use crossterm::style::StyledContent;
+
+use crate::colour::Palette;
+
+/// Gutter
+/// The line numbers drawn left of the text, right-aligned in a column as
+/// wide as the number of the last line.
+///
+/// In relative mode, the line of the cursor shows its own number and every
+/// other line its distance from it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Gutter {
+    /// Whether the line numbers are shown
+    pub shown: bool,
+    /// Whether the line numbers are relative to the line of the cursor
+    pub relative: bool,
+}
+
+impl Gutter {
+    /// Get the width of the gutter for a buffer of `lines` lines
+    /// The numbers are followed by a space, separating them from the text.
+    pub fn width(&self, lines: usize) -> usize {
+        if !self.shown {
+            return 0;
+        }
+        lines.max(1).ilog10() as usize + 2
+    }
+
+    /// Render the gutter of a row of the buffer, both zero-based
+    pub fn render(
+        &self,
+        row: usize,
+        cursor_row: usize,
+        width: usize,
+        palette: &Palette,
+    ) -> StyledContent<String> {
+        if width == 0 {
+            return palette.paint(palette.numbers, "");
+        }
+
+        let number = if self.relative && row != cursor_row {
+            row.abs_diff(cursor_row)
+        } else {
+            row + 1
+        };
+        let digits = width - 1;
+        palette.paint(palette.numbers, &format!("{:>digits$} ", number))
+    }
+
+    /// Render the gutter of a row continuing a soft wrapped line, left blank
+    pub fn blank(&self, width: usize, palette: &Palette) -> StyledContent<String> {
+        palette.paint(palette.numbers, &" ".repeat(width))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syntect::highlighting::Theme;
+
+    use super::*;
+    use crate::colour::ColourDepth;
+
+    fn palette() -> Palette {
+        Palette::from_theme(&Theme::default(), ColourDepth::Monochrome)
+    }
+
+    #[test]
+    fn test_width_grows_with_lines() {
+        let gutter = Gutter {
+            shown: true,
+            relative: false,
+        };
+        assert_eq!(gutter.width(0), 2);
+        assert_eq!(gutter.width(9), 2);
+        assert_eq!(gutter.width(10), 3);
+        assert_eq!(gutter.width(12345), 6);
+        assert_eq!(Gutter::default().width(12345), 0);
+    }
+
+    #[test]
+    fn test_render_right_aligned() {
+        let gutter = Gutter {
+            shown: true,
+            relative: false,
+        };
+        assert_eq!(gutter.render(8, 0, 4, &palette()).content(), "  9 ");
+        assert_eq!(gutter.render(8, 0, 0, &palette()).content(), "");
+        assert_eq!(gutter.blank(4, &palette()).content(), "    ");
+    }
+
+    #[test]
+    fn test_render_relative() {
+        let gutter = Gutter {
+            shown: true,
+            relative: true,
+        };
+        assert_eq!(gutter.render(2, 5, 3, &palette()).content(), " 3 ");
+        assert_eq!(gutter.render(5, 5, 3, &palette()).content(), " 6 ");
+        assert_eq!(gutter.render(9, 5, 3, &palette()).content(), " 4 ");
+    }
+}
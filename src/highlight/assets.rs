@@ -0,0 +1,427 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Instant, UNIX_EPOCH};
+
+use syntect::dumps;
+use syntect::highlighting::ThemeSet;
+use syntect::parsing::{SyntaxDefinition, SyntaxSet};
+
+use super::nanorc;
+use crate::error::{NanoError, NanoResult};
+
+/// The name of the cached syntax set in the cache directory
+const SYNTAX_DUMP: &str = "syntaxes.packdump";
+
+/// The name of the cached theme set in the cache directory
+const THEME_DUMP: &str = "themes.themedump";
+
+/// The name of the file describing the sources of the cached assets
+const FINGERPRINT: &str = "assets.fingerprint";
+
+/// Assets
+/// The syntaxes and themes available for highlighting: syntect's defaults
+/// merged with the user's own `.sublime-syntax` and `.tmTheme` files, and
+/// the user's GNU nano `.nanorc` syntaxes.
+///
+/// Merging syntaxes means building the whole syntax set again, which is
+/// slow, so the merged sets are dumped to the cache directory and loaded from
+/// there as long as the user's files are unchanged.
+#[derive(Debug)]
+pub struct Assets {
+    pub syntax_set: SyntaxSet,
+    pub theme_set: ThemeSet,
+    pub nanorc: Vec<nanorc::Syntax>,
+}
+
+impl Assets {
+    /// Load the default assets and the user's files from the given
+    /// directories, using the dumps in the cache directory when they are up
+    /// to date
+    /// A file that fails to load is skipped, and reported in the returned
+    /// errors along with its path.
+    pub fn load(
+        syntax_dir: Option<&Path>,
+        theme_dir: Option<&Path>,
+        nanorc_dir: Option<&Path>,
+        cache_dir: Option<&Path>,
+    ) -> (Self, Vec<NanoError>) {
+        let start = Instant::now();
+        // The nanorc syntaxes are not cached, so their errors are kept apart
+        let mut nanorc_errors = Vec::new();
+        let nanorc_files = nanorc_dir.map_or_else(Vec::new, |dir| {
+            find_files(dir, "nanorc", &mut nanorc_errors)
+        });
+        let nanorc = load_nanorc_syntaxes(nanorc_files, &mut nanorc_errors);
+
+        let mut errors = Vec::new();
+        let syntax_files = syntax_dir.map_or_else(Vec::new, |dir| {
+            find_files(dir, "sublime-syntax", &mut errors)
+        });
+        let theme_files =
+            theme_dir.map_or_else(Vec::new, |dir| find_files(dir, "tmTheme", &mut errors));
+
+        if syntax_files.is_empty() && theme_files.is_empty() {
+            let assets = Self {
+                syntax_set: SyntaxSet::load_defaults_newlines(),
+                theme_set: ThemeSet::load_defaults(),
+                nanorc,
+            };
+            errors.extend(nanorc_errors);
+            return (assets, errors);
+        }
+
+        let fingerprint = fingerprint(syntax_files.iter().chain(&theme_files));
+        if let Some(assets) = cache_dir.and_then(|dir| Assets::from_cache(dir, &fingerprint)) {
+            log::debug!("Loaded cached assets in {:?}", start.elapsed());
+            errors.extend(nanorc_errors);
+            return (Self { nanorc, ..assets }, errors);
+        }
+
+        let assets = Self {
+            syntax_set: load_syntax_set(syntax_files, &mut errors),
+            theme_set: load_theme_set(theme_files, &mut errors),
+            nanorc,
+        };
+
+        // Broken files are not cached, so that they are reported until fixed
+        if let (Some(dir), true) = (cache_dir, errors.is_empty()) {
+            if let Err(e) = assets.write_cache(dir, &fingerprint) {
+                log::warn!("Failed to cache assets in {}: {}", dir.display(), e);
+            }
+        }
+        log::debug!("Built assets in {:?}", start.elapsed());
+
+        errors.extend(nanorc_errors);
+        (assets, errors)
+    }
+
+    /// Load the assets dumped in the cache directory, if they were built
+    /// from the files described by the fingerprint
+    /// The nanorc syntaxes are quick to parse, so they are not cached.
+    fn from_cache(dir: &Path, fingerprint: &str) -> Option<Self> {
+        let cached = fs::read_to_string(dir.join(FINGERPRINT)).ok()?;
+        if cached != fingerprint {
+            return None;
+        }
+
+        let assets =
+            dumps::from_uncompressed_dump_file(dir.join(SYNTAX_DUMP)).and_then(|syntax_set| {
+                let theme_set = dumps::from_uncompressed_dump_file(dir.join(THEME_DUMP))?;
+                Ok(Self {
+                    syntax_set,
+                    theme_set,
+                    nanorc: Vec::new(),
+                })
+            });
+
+        match assets {
+            Ok(assets) => Some(assets),
+            Err(e) => {
+                log::warn!("Failed to load cached assets from {}: {}", dir.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Dump the assets to the cache directory
+    /// The fingerprint is written last, so that an interrupted write is never
+    /// mistaken for an up to date cache.
+    fn write_cache(&self, dir: &Path, fingerprint: &str) -> NanoResult<()> {
+        fs::create_dir_all(dir)?;
+        let _ = fs::remove_file(dir.join(FINGERPRINT));
+
+        dumps::dump_to_uncompressed_file(&self.syntax_set, dir.join(SYNTAX_DUMP))
+            .and_then(|_| dumps::dump_to_uncompressed_file(&self.theme_set, dir.join(THEME_DUMP)))
+            .map_err(|e| NanoError::Generic(e.to_string()))?;
+        fs::write(dir.join(FINGERPRINT), fingerprint)?;
+
+        Ok(())
+    }
+}
+
+/// Describe the source files of the assets, so that a change to any of them
+/// (or to the editor, which embeds syntect's defaults) invalidates the cache
+fn fingerprint<'a, I: IntoIterator<Item = &'a PathBuf>>(files: I) -> String {
+    let mut fingerprint = format!("nano-rs {}\n", env!("CARGO_PKG_VERSION"));
+
+    for path in files {
+        let (size, modified) = fs::metadata(path).map_or((0, 0), |metadata| {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map_or(0, |duration| duration.as_nanos());
+            (metadata.len(), modified)
+        });
+        fingerprint.push_str(&format!("{} {} {}\n", path.display(), size, modified));
+    }
+
+    fingerprint
+}
+
+/// Load the default syntaxes merged with the given `.sublime-syntax` files
+fn load_syntax_set(files: Vec<PathBuf>, errors: &mut Vec<NanoError>) -> SyntaxSet {
+    let mut builder = SyntaxSet::load_defaults_newlines().into_builder();
+    for path in files {
+        let definition = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| {
+                let name = path.file_stem().map(|stem| stem.to_string_lossy());
+                SyntaxDefinition::load_from_str(&contents, true, name.as_deref())
+                    .map_err(|e| e.to_string())
+            });
+
+        match definition {
+            Ok(definition) => builder.add(definition),
+            Err(e) => errors.push(NanoError::Asset(path, e)),
+        }
+    }
+
+    builder.build()
+}
+
+/// Load the default themes merged with the given `.tmTheme` files
+/// Themes are named after their file, without the extension.
+fn load_theme_set(files: Vec<PathBuf>, errors: &mut Vec<NanoError>) -> ThemeSet {
+    let mut theme_set = ThemeSet::load_defaults();
+
+    for path in files {
+        match ThemeSet::get_theme(&path) {
+            Ok(theme) => {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                theme_set.themes.insert(name.to_string(), theme);
+            }
+            Err(e) => errors.push(NanoError::Asset(path, e.to_string())),
+        }
+    }
+
+    theme_set
+}
+
+/// Load the syntaxes of the given `.nanorc` files
+fn load_nanorc_syntaxes(files: Vec<PathBuf>, errors: &mut Vec<NanoError>) -> Vec<nanorc::Syntax> {
+    let mut syntaxes = Vec::new();
+    for path in files {
+        let parsed = fs::read_to_string(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|contents| nanorc::parse(&contents));
+
+        match parsed {
+            Ok(parsed) => syntaxes.extend(parsed),
+            Err(e) => errors.push(NanoError::Asset(path, e)),
+        }
+    }
+
+    syntaxes
+}
+
+/// Find the files with the given extension in a directory and its
+/// subdirectories, in a stable order
+/// A missing directory is not an error, as the user might have no files.
+fn find_files(dir: &Path, extension: &str, errors: &mut Vec<NanoError>) -> Vec<PathBuf> {
+    find_files_in(dir, extension, &mut HashSet::new(), errors)
+}
+
+/// Find the files with the given extension in a directory not visited yet
+/// Directories are visited by their canonical path, so that a symbolic link
+/// looping back to a parent directory is only followed once.
+fn find_files_in(
+    dir: &Path,
+    extension: &str,
+    visited: &mut HashSet<PathBuf>,
+    errors: &mut Vec<NanoError>,
+) -> Vec<PathBuf> {
+    if let Ok(canonical) = fs::canonicalize(dir) {
+        if !visited.insert(canonical) {
+            return Vec::new();
+        }
+    }
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Vec::new(),
+        Err(e) => {
+            errors.push(NanoError::Asset(dir.to_path_buf(), e.to_string()));
+            return Vec::new();
+        }
+    };
+
+    let mut paths = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            files.extend(find_files_in(&path, extension, visited, errors));
+        } else if path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYNTAX: &str = r#"%YAML 1.2
+---
+name: Nanotest
+file_extensions: [nanotest]
+scope: source.nanotest
+contexts:
+  main:
+    - match: '\bkeyword\b'
+      scope: keyword.control.nanotest
+"#;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nano-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_load_defaults() {
+        let (assets, errors) =
+            Assets::load(None, Some(Path::new("/nonexistent/nano-rs")), None, None);
+        assert!(errors.is_empty());
+        assert!(assets.syntax_set.find_syntax_by_extension("rs").is_some());
+        assert!(assets.theme_set.themes.contains_key("base16-mocha.dark"));
+    }
+
+    #[test]
+    fn test_load_user_syntaxes() {
+        let dir = test_directory("syntaxes");
+        fs::write(dir.join("nested/nanotest.sublime-syntax"), SYNTAX).unwrap();
+        fs::write(dir.join("broken.sublime-syntax"), "name: [").unwrap();
+        fs::write(dir.join("notes.txt"), "not a syntax").unwrap();
+
+        let (assets, errors) = Assets::load(Some(&dir), None, None, None);
+        assert!(assets
+            .syntax_set
+            .find_syntax_by_extension("nanotest")
+            .is_some());
+        assert!(assets.syntax_set.find_syntax_by_extension("rs").is_some());
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("broken.sublime-syntax"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_files_symlink_loop() {
+        let dir = test_directory("loop");
+        fs::write(dir.join("nested/a.nanorc"), "").unwrap();
+        std::os::unix::fs::symlink(&dir, dir.join("nested/parent")).unwrap();
+
+        let mut errors = Vec::new();
+        let files = find_files(&dir, "nanorc", &mut errors);
+        assert_eq!(files, [dir.join("nested/a.nanorc")]);
+        assert!(errors.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_broken_theme() {
+        let dir = test_directory("themes");
+        fs::write(dir.join("broken.tmTheme"), "<plist>").unwrap();
+
+        let (assets, errors) = Assets::load(None, Some(&dir), None, None);
+        assert!(!assets.theme_set.themes.contains_key("broken"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("broken.tmTheme"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = test_directory("cache");
+        let syntax_dir = dir.join("nested");
+        let cache_dir = dir.join("cache");
+        fs::write(syntax_dir.join("nanotest.sublime-syntax"), SYNTAX).unwrap();
+
+        let (_, errors) = Assets::load(Some(&syntax_dir), None, None, Some(&cache_dir));
+        assert!(errors.is_empty());
+        let cached = fs::read_to_string(cache_dir.join(FINGERPRINT)).unwrap();
+        assert!(cached.contains("nanotest.sublime-syntax"));
+
+        let assets = Assets::from_cache(&cache_dir, &cached).unwrap();
+        assert!(assets
+            .syntax_set
+            .find_syntax_by_extension("nanotest")
+            .is_some());
+        assert!(assets.theme_set.themes.contains_key("base16-mocha.dark"));
+
+        // Changing a source file invalidates the cache
+        let path = syntax_dir.join("nanotest.sublime-syntax");
+        fs::write(&path, format!("{}# changed\n", SYNTAX)).unwrap();
+        assert_ne!(fingerprint([&path]), cached);
+        assert!(Assets::from_cache(&cache_dir, &fingerprint([&path])).is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_reads_cache() {
+        let dir = test_directory("cached");
+        let syntax_dir = dir.join("nested");
+        let cache_dir = dir.join("cache");
+        let nanorc_dir = dir.join("nanorc");
+        fs::write(syntax_dir.join("nanotest.sublime-syntax"), SYNTAX).unwrap();
+        fs::create_dir_all(&nanorc_dir).unwrap();
+        fs::write(nanorc_dir.join("broken.nanorc"), "color red \"x\"\n").unwrap();
+
+        // A broken nanorc file does not keep the syntaxes from being cached
+        let load = || Assets::load(Some(&syntax_dir), None, Some(&nanorc_dir), Some(&cache_dir));
+        let (assets, errors) = load();
+        assert_eq!(errors.len(), 1);
+        assert!(assets
+            .syntax_set
+            .find_syntax_by_extension("nanotest")
+            .is_some());
+        assert!(cache_dir.join(FINGERPRINT).exists());
+
+        // The dump is loaded rather than the syntax files
+        dumps::dump_to_uncompressed_file(
+            &SyntaxSet::load_defaults_newlines(),
+            cache_dir.join(SYNTAX_DUMP),
+        )
+        .unwrap();
+        let (assets, errors) = load();
+        assert_eq!(errors.len(), 1);
+        assert!(assets
+            .syntax_set
+            .find_syntax_by_extension("nanotest")
+            .is_none());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_load_nanorc_syntaxes() {
+        let dir = test_directory("nanorc");
+        fs::write(
+            dir.join("nested/a.nanorc"),
+            "syntax a \"\\.a$\"\ncolor red \"x\"\n",
+        )
+        .unwrap();
+        fs::write(dir.join("broken.nanorc"), "color red \"x\"\n").unwrap();
+
+        let (assets, errors) = Assets::load(None, None, Some(&dir), None);
+        assert_eq!(assets.nanorc.len(), 1);
+        assert_eq!(assets.nanorc[0].name, "a");
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("broken.nanorc: line 1"));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,645 @@
+pub mod assets;
+pub mod nanorc;
+pub mod treesitter;
+pub mod worker;
+
+use std::ops::Range;
+use std::path::Path;
+
+use serde_derive::Deserialize;
+use syntect::highlighting::{HighlightIterator, HighlightState, Highlighter as ThemeHighlighter};
+use syntect::highlighting::{Style, Theme};
+use syntect::parsing::{ParseState, ScopeStack, SyntaxReference, SyntaxSet};
+
+use crate::buffer::Buffer;
+use crate::content::UnicodeSegmentation;
+use crate::error::{NanoError, NanoResult};
+
+/// The number of lines between two saved parser states
+const CHECKPOINT_INTERVAL: usize = 16;
+
+/// A styled byte range of a line
+pub type Span = (Style, Range<usize>);
+
+/// Engine
+/// The highlighting engines, chosen per file type in the configuration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Engine {
+    /// Sublime Text syntaxes
+    #[default]
+    Syntect,
+    /// GNU nano `.nanorc` syntaxes
+    Nanorc,
+    /// tree-sitter grammars, for a few languages
+    #[serde(rename = "tree-sitter")]
+    TreeSitter,
+}
+
+/// The highlighting state at the start of a line
+#[derive(Debug, Clone)]
+enum State {
+    Syntect {
+        parse: ParseState,
+        highlight: HighlightState,
+    },
+    /// The state of the nanorc syntax at index `syntax`
+    Nanorc { syntax: usize, state: nanorc::State },
+    /// A tree-sitter grammar, whose state is the syntax tree of the whole
+    /// buffer rather than of a line
+    TreeSitter { grammar: usize },
+}
+
+/// Highlighter
+/// Highlights the lines of a buffer with syntect, a nanorc syntax or a
+/// tree-sitter grammar, keeping the syntaxes and theme loaded for the whole
+/// session.
+///
+/// Lines are highlighted in order, as the state at the end of a line (an open
+/// block comment, a string) carries over to the next one. The state is saved
+/// every `CHECKPOINT_INTERVAL` lines so that an edit only re-highlights the
+/// lines from the checkpoint before it. The spans are handed to the caller,
+/// which keeps them; see `worker::HighlightWorker`.
+#[derive(Debug)]
+pub struct Highlighter {
+    syntax_set: SyntaxSet,
+    nanorc: Vec<nanorc::Syntax>,
+    /// The tree-sitter syntax in use, prepared on the first highlight
+    tree_sitter: Option<treesitter::Syntax>,
+    theme: Theme,
+    syntax_name: String,
+    /// `checkpoints[n]` is the state at the start of line
+    /// `n * CHECKPOINT_INTERVAL`
+    checkpoints: Vec<State>,
+    /// The number of lines highlighted so far
+    highlighted: usize,
+    /// The state at the start of line `highlighted`
+    state: State,
+}
+
+impl Highlighter {
+    /// Create a highlighter
+    /// Until a syntax is set, lines are highlighted as plain text.
+    pub fn new(syntax_set: SyntaxSet, nanorc: Vec<nanorc::Syntax>, theme: Theme) -> Self {
+        let syntax = syntax_set.find_syntax_plain_text();
+        let syntax_name = syntax.name.clone();
+        let state = Highlighter::syntect_state(&theme, syntax);
+
+        Self {
+            syntax_set,
+            nanorc,
+            tree_sitter: None,
+            theme,
+            syntax_name,
+            checkpoints: Vec::new(),
+            highlighted: 0,
+            state,
+        }
+    }
+
+    /// Get the name of the syntax in use
+    pub fn syntax_name(&self) -> &str {
+        &self.syntax_name
+    }
+
+    /// Highlight with the syntax detected for the buffer
+    /// The syntaxes of the given engine are tried first, then those of the
+    /// other one. This will return whether a syntax was found, the
+    /// highlighter keeps its current syntax otherwise.
+    pub fn detect_syntax(&mut self, buffer: &Buffer, engine: Engine) -> bool {
+        let syntect = || {
+            let syntax = find_syntax(&self.syntax_set, buffer)?;
+            Some((
+                syntax.name.clone(),
+                Highlighter::syntect_state(&self.theme, syntax),
+            ))
+        };
+        let nanorc = || {
+            let syntax = nanorc::find_syntax(&self.nanorc, buffer)?;
+            Some(self.nanorc_state(syntax))
+        };
+        let tree_sitter = || treesitter::find_grammar(buffer.file_type()).map(tree_sitter_state);
+
+        let found = match engine {
+            Engine::Syntect => syntect().or_else(nanorc).or_else(tree_sitter),
+            Engine::Nanorc => nanorc().or_else(syntect).or_else(tree_sitter),
+            Engine::TreeSitter => tree_sitter().or_else(syntect).or_else(nanorc),
+        };
+        found.map(|(name, state)| self.start(name, state)).is_some()
+    }
+
+    /// Highlight with the syntax of the given name or file extension
+    /// syntect's syntaxes are looked up first, then the nanorc ones and the
+    /// tree-sitter grammars. This will return whether a syntax was found, the
+    /// highlighter keeps its current syntax otherwise.
+    pub fn set_syntax(&mut self, name: &str) -> bool {
+        let found = match self.syntax_set.find_syntax_by_token(name) {
+            Some(syntax) => Some((
+                syntax.name.clone(),
+                Highlighter::syntect_state(&self.theme, syntax),
+            )),
+            None => self
+                .nanorc
+                .iter()
+                .position(|syntax| syntax.name.eq_ignore_ascii_case(name))
+                .map(|syntax| self.nanorc_state(syntax))
+                .or_else(|| treesitter::find_grammar_by_token(name).map(tree_sitter_state)),
+        };
+        found.map(|(name, state)| self.start(name, state)).is_some()
+    }
+
+    /// Start highlighting from the first line with a syntax
+    fn start(&mut self, syntax_name: String, state: State) {
+        self.syntax_name = syntax_name;
+        self.state = state;
+        self.tree_sitter = None;
+        self.checkpoints.clear();
+        self.highlighted = 0;
+    }
+
+    fn syntect_state(theme: &Theme, syntax: &SyntaxReference) -> State {
+        let highlighter = ThemeHighlighter::new(theme);
+        State::Syntect {
+            parse: ParseState::new(syntax),
+            highlight: HighlightState::new(&highlighter, ScopeStack::new()),
+        }
+    }
+
+    fn nanorc_state(&self, syntax: usize) -> (String, State) {
+        let state = State::Nanorc {
+            syntax,
+            state: self.nanorc[syntax].initial_state(),
+        };
+        (self.nanorc[syntax].name.clone(), state)
+    }
+
+    /// Get the style of text that is not highlighted
+    pub fn default_style(&self) -> Style {
+        ThemeHighlighter::new(&self.theme).get_default()
+    }
+
+    /// Get the number of lines highlighted so far
+    pub fn highlighted(&self) -> usize {
+        self.highlighted
+    }
+
+    /// Highlight the lines of the buffer up to, but not including, `end`
+    /// Lines that are already highlighted are not highlighted again, so this
+    /// returns the spans of the lines from `highlighted()` on.
+    pub fn highlight_to(&mut self, buffer: &Buffer, end: usize) -> NanoResult<Vec<Vec<Span>>> {
+        let end = end.min(buffer.len());
+        let highlighter = ThemeHighlighter::new(&self.theme);
+        let mut lines = Vec::new();
+
+        // tree-sitter parses the whole buffer, the lines are only queried
+        if let State::TreeSitter { grammar } = self.state {
+            if self.highlighted >= end {
+                return Ok(lines);
+            }
+            let syntax = match &mut self.tree_sitter {
+                Some(syntax) => syntax,
+                None => self.tree_sitter.insert(
+                    treesitter::Syntax::new(grammar, &self.theme).map_err(NanoError::Generic)?,
+                ),
+            };
+            lines = syntax
+                .highlight_lines(buffer, self.highlighted..end, highlighter.get_default())
+                .map_err(NanoError::Generic)?;
+            self.highlighted = end;
+            return Ok(lines);
+        }
+
+        while self.highlighted < end {
+            let row = self.highlighted;
+            if row.is_multiple_of(CHECKPOINT_INTERVAL)
+                && self.checkpoints.len() == row / CHECKPOINT_INTERVAL
+            {
+                self.checkpoints.push(self.state.clone());
+            }
+
+            let text = &buffer.data[row].text;
+            let spans = match &mut self.state {
+                State::Syntect { parse, highlight } => {
+                    // The syntaxes are loaded for lines that include the line break
+                    let line = format!("{}\n", text);
+                    let ops = parse
+                        .parse_line(&line, &self.syntax_set)
+                        .map_err(syntect::Error::from)?;
+                    let mut offset = 0;
+                    HighlightIterator::new(highlight, &ops, &line, &highlighter)
+                        .map(|(style, text)| {
+                            offset += text.len();
+                            (style, offset - text.len()..offset)
+                        })
+                        .collect()
+                }
+                State::Nanorc { syntax, state } => {
+                    self.nanorc[*syntax].highlight_line(text, state, highlighter.get_default())
+                }
+                State::TreeSitter { .. } => unreachable!("tree-sitter highlights whole buffers"),
+            };
+
+            lines.push(spans);
+            self.highlighted += 1;
+        }
+
+        Ok(lines)
+    }
+
+    /// Replace `removed` lines of the buffer from `row` with `inserted`
+    /// The lines from the edited one on are highlighted again. A tree-sitter
+    /// tree is parsed again right away, and the lines from the first one
+    /// whose nodes changed are highlighted again, even above the edit.
+    pub fn edit(&mut self, buffer: &mut Buffer, row: usize, removed: usize, inserted: Vec<String>) {
+        if let Some(syntax) = &mut self.tree_sitter {
+            syntax.edit(&treesitter::line_edit(buffer, row, removed, &inserted));
+        }
+        buffer.replace_lines(row, removed, inserted);
+
+        let changed = match (&self.state, &mut self.tree_sitter) {
+            (State::TreeSitter { .. }, Some(syntax)) => syntax.reparse(buffer),
+            _ => None,
+        };
+        self.invalidate(changed.map_or(row, |changed| changed.min(row)));
+    }
+
+    /// Forget the highlighting from a line onwards, after it was edited
+    pub fn invalidate(&mut self, row: usize) {
+        if row >= self.highlighted {
+            return;
+        }
+        if let State::TreeSitter { .. } = self.state {
+            self.highlighted = row;
+            return;
+        }
+
+        let checkpoint = row / CHECKPOINT_INTERVAL;
+        self.state = self.checkpoints[checkpoint].clone();
+        self.checkpoints.truncate(checkpoint + 1);
+        self.highlighted = checkpoint * CHECKPOINT_INTERVAL;
+    }
+}
+
+/// Get the highlighting state of a tree-sitter grammar
+fn tree_sitter_state(grammar: usize) -> (String, State) {
+    let name = treesitter::grammar_name(grammar).to_string();
+    (name, State::TreeSitter { grammar })
+}
+
+/// Get the spans of a line of `len` bytes in a single style
+fn plain(style: Style, len: usize) -> Vec<Span> {
+    if len == 0 {
+        Vec::new()
+    } else {
+        vec![(style, 0..len)]
+    }
+}
+
+/// Paint a range of a line over its spans
+/// The spans cover the line in order, and neighbours of the same style are
+/// merged.
+fn paint(spans: &mut Vec<Span>, style: Style, range: Range<usize>) {
+    if range.is_empty() {
+        return;
+    }
+
+    let mut painted: Vec<Span> = Vec::with_capacity(spans.len() + 2);
+    let mut push = |style: Style, range: Range<usize>| match painted.last_mut() {
+        Some((last, previous)) if *last == style && previous.end == range.start => {
+            previous.end = range.end
+        }
+        _ => painted.push((style, range)),
+    };
+    let mut pending = Some(range.clone());
+    for (old, span) in spans.drain(..) {
+        if span.start < range.start {
+            push(old, span.start..span.end.min(range.start));
+        }
+        if span.end > range.start {
+            if let Some(range) = pending.take() {
+                push(style, range);
+            }
+        }
+        if span.end > range.end {
+            push(old, span.start.max(range.end)..span.end);
+        }
+    }
+    if let Some(range) = pending {
+        push(style, range);
+    }
+    *spans = painted;
+}
+
+/// Interpreters whose name is neither the name nor an extension of their
+/// syntax
+const INTERPRETERS: &[(&str, &str)] = &[("node", "js"), ("nodejs", "js"), ("python", "py")];
+
+/// Find the syntax of a buffer
+/// The syntax is looked up by the full file name first (`Makefile`,
+/// `.bashrc`), then by extension, then by the interpreter of a `#!` line and
+/// finally by the first line patterns of the syntaxes (`<?xml`, `<?php`).
+pub fn find_syntax<'a>(syntax_set: &'a SyntaxSet, buffer: &Buffer) -> Option<&'a SyntaxReference> {
+    let file_name = buffer
+        .name
+        .as_deref()
+        .and_then(|name| Path::new(name).file_name())
+        .map(|name| name.to_string_lossy());
+    let first_line = buffer.row(0).map_or("", |content| content.text.as_str());
+
+    file_name
+        .and_then(|name| syntax_set.find_syntax_by_extension(&name))
+        .or_else(|| syntax_set.find_syntax_by_extension(buffer.file_type()))
+        .or_else(|| {
+            let interpreter = interpreter(first_line)?;
+            let token = INTERPRETERS
+                .iter()
+                .find(|(name, _)| *name == interpreter)
+                .map_or(interpreter, |(_, extension)| extension);
+            syntax_set.find_syntax_by_token(token)
+        })
+        .or_else(|| syntax_set.find_syntax_by_first_line(first_line))
+}
+
+/// Get the interpreter of a `#!` line, without its version
+/// `#!/usr/bin/env python3` gives `python`.
+fn interpreter(line: &str) -> Option<&str> {
+    let mut words = line.strip_prefix("#!")?.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    let program = program.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.');
+    (!program.is_empty()).then_some(program)
+}
+
+/// Clip the spans of a line to the graphemes in `start..end`
+/// This is used to show the part of a line that fits in the terminal.
+pub fn clip<'a>(text: &'a str, spans: &[Span], start: usize, end: usize) -> Vec<(Style, &'a str)> {
+    let mut graphemes = text
+        .grapheme_indices(true)
+        .skip(start)
+        .take(end.saturating_sub(start));
+    let Some((first, _)) = graphemes.next() else {
+        return Vec::new();
+    };
+    let last = graphemes.last().map_or(first, |(offset, _)| offset);
+    let last = last + text[last..].graphemes(true).next().map_or(0, str::len);
+
+    spans
+        .iter()
+        .filter_map(|(style, range)| {
+            let from = range.start.max(first);
+            let to = range.end.min(last);
+            (from < to).then(|| (*style, &text[from..to]))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use syntect::highlighting::ThemeSet;
+
+    use super::*;
+    use crate::content::Data;
+
+    fn highlighter(name: &str) -> Option<Highlighter> {
+        let theme = ThemeSet::load_defaults().themes["base16-mocha.dark"].clone();
+        let mut highlighter =
+            Highlighter::new(SyntaxSet::load_defaults_newlines(), Vec::new(), theme);
+        highlighter.set_syntax(name).then_some(highlighter)
+    }
+
+    fn buffer(lines: &[&str]) -> Buffer {
+        Buffer {
+            data: lines.iter().map(|line| Data::from(*line)).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_paint() {
+        let (default, mut red) = (Style::default(), Style::default());
+        red.foreground.r = 255;
+
+        let mut spans = plain(default, 10);
+        paint(&mut spans, red, 2..4);
+        assert_eq!(spans, [(default, 0..2), (red, 2..4), (default, 4..10)]);
+        paint(&mut spans, red, 4..6);
+        assert_eq!(spans, [(default, 0..2), (red, 2..6), (default, 6..10)]);
+        paint(&mut spans, default, 0..10);
+        assert_eq!(spans, plain(default, 10));
+        paint(&mut spans, red, 8..10);
+        assert_eq!(spans, [(default, 0..8), (red, 8..10)]);
+        assert!(plain(default, 0).is_empty());
+    }
+
+    #[test]
+    fn test_unknown_syntax() {
+        assert!(highlighter("not-a-language").is_none());
+    }
+
+    #[test]
+    fn test_plain_text_fallback() {
+        let theme = ThemeSet::load_defaults().themes["base16-mocha.dark"].clone();
+        let mut highlighter =
+            Highlighter::new(SyntaxSet::load_defaults_newlines(), Vec::new(), theme);
+        let buffer = buffer(&["all: build", "\tcargo build"]);
+        assert!(!highlighter.detect_syntax(&buffer, Engine::Syntect));
+        assert_eq!(highlighter.syntax_name(), "Plain Text");
+
+        let lines = highlighter.highlight_to(&buffer, 2).unwrap();
+        assert_eq!(lines[1].len(), 1);
+        assert_eq!(lines[1][0].1, 0..buffer.data[1].text.len() + 1);
+    }
+
+    #[test]
+    fn test_engine_preference() {
+        let theme = ThemeSet::load_defaults().themes["base16-mocha.dark"].clone();
+        let nanorc = nanorc::parse("syntax rusty \"\\.rs$\"\ncolor red \"fn\"").unwrap();
+        let mut highlighter = Highlighter::new(SyntaxSet::load_defaults_newlines(), nanorc, theme);
+        let buffer = Buffer {
+            name: Some("main.rs".to_string()),
+            data: vec![Data::from("fn main() {}")],
+            buffer_type: "rs".to_string(),
+            ..Default::default()
+        };
+
+        assert!(highlighter.detect_syntax(&buffer, Engine::Syntect));
+        assert_eq!(highlighter.syntax_name(), "Rust");
+        assert!(highlighter.detect_syntax(&buffer, Engine::Nanorc));
+        assert_eq!(highlighter.syntax_name(), "rusty");
+
+        let lines = highlighter.highlight_to(&buffer, 1).unwrap();
+        let spans = &lines[0];
+        assert_eq!(spans[0].1, 0..2);
+        assert_eq!(spans.last().unwrap().1.end, "fn main() {}".len());
+
+        assert!(highlighter.set_syntax("Rust"));
+        assert!(highlighter.set_syntax("RUSTY"));
+        assert_eq!(highlighter.syntax_name(), "rusty");
+    }
+
+    #[test]
+    fn test_tree_sitter_engine() {
+        let theme = ThemeSet::load_defaults().themes["base16-mocha.dark"].clone();
+        let mut highlighter =
+            Highlighter::new(SyntaxSet::load_defaults_newlines(), Vec::new(), theme);
+        let mut buffer = Buffer {
+            name: Some("Cargo.toml".to_string()),
+            buffer_type: "toml".to_string(),
+            ..buffer(&["[package]", "name = \"nano\""])
+        };
+
+        // syntect has no TOML syntax, tree-sitter has
+        assert!(highlighter.detect_syntax(&buffer, Engine::Syntect));
+        assert_eq!(highlighter.syntax_name(), "TOML");
+        let lines = highlighter.highlight_to(&buffer, 10).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(highlighter.highlighted(), 2);
+
+        highlighter.invalidate(1);
+        assert_eq!(highlighter.highlighted(), 1);
+        assert_eq!(highlighter.highlight_to(&buffer, 2).unwrap(), lines[1..]);
+
+        buffer.buffer_type = "rs".to_string();
+        assert!(highlighter.detect_syntax(&buffer, Engine::TreeSitter));
+        assert!(matches!(highlighter.state, State::TreeSitter { .. }));
+    }
+
+    #[test]
+    fn test_tree_sitter_edit_changes_lines_above() {
+        let tree_sitter = |buffer: &Buffer| {
+            let theme = ThemeSet::load_defaults().themes["base16-mocha.dark"].clone();
+            let mut highlighter =
+                Highlighter::new(SyntaxSet::load_defaults_newlines(), Vec::new(), theme);
+            assert!(highlighter.detect_syntax(buffer, Engine::TreeSitter));
+            highlighter
+        };
+        let mut buffer = Buffer {
+            buffer_type: "rs".to_string(),
+            ..buffer(&["fn a() {}", "/* x", "fn b() {}", "fn c() {}"])
+        };
+        let mut highlighter = tree_sitter(&buffer);
+        highlighter.highlight_to(&buffer, 4).unwrap();
+
+        // Closing the comment below changes the line opening it
+        highlighter.edit(&mut buffer, 2, 1, vec!["fn b() {} */".to_string()]);
+        assert_eq!(highlighter.highlighted(), 1);
+        let lines = highlighter.highlight_to(&buffer, 4).unwrap();
+        let fresh = tree_sitter(&buffer).highlight_to(&buffer, 4).unwrap();
+        assert_eq!(lines, fresh[1..]);
+    }
+
+    #[test]
+    fn test_spans_cover_line() {
+        let buffer = buffer(&["fn main() {}"]);
+        let mut highlighter = highlighter("rs").unwrap();
+        let lines = highlighter.highlight_to(&buffer, 10).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(highlighter.highlighted(), 1);
+
+        let spans = &lines[0];
+        assert_eq!(spans.first().unwrap().1.start, 0);
+        assert_eq!(spans.last().unwrap().1.end, "fn main() {}\n".len());
+        assert!(highlighter.highlight_to(&buffer, 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_state_carries_across_lines() {
+        let buffer = buffer(&["/* start", "fn main() {}", "end */"]);
+        let mut highlighter = highlighter("rs").unwrap();
+        let lines = highlighter.highlight_to(&buffer, 3).unwrap();
+
+        // The whole second line is inside the block comment
+        let comment = lines[0][0].0;
+        assert!(lines[1].iter().all(|(style, _)| *style == comment));
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let lines = vec!["let x = 1;"; CHECKPOINT_INTERVAL * 2];
+        let buffer = buffer(&lines);
+        let mut highlighter = highlighter("rs").unwrap();
+        let before = highlighter.highlight_to(&buffer, buffer.len()).unwrap();
+
+        highlighter.invalidate(CHECKPOINT_INTERVAL + 3);
+        assert_eq!(highlighter.highlighted(), CHECKPOINT_INTERVAL);
+
+        let after = highlighter.highlight_to(&buffer, buffer.len()).unwrap();
+        assert_eq!(after.len(), CHECKPOINT_INTERVAL);
+        assert_eq!(after[3], before[CHECKPOINT_INTERVAL + 3]);
+    }
+
+    #[test]
+    fn test_set_syntax_by_name() {
+        assert_eq!(highlighter("Rust").unwrap().syntax_name(), "Rust");
+        assert_eq!(highlighter("python").unwrap().syntax_name(), "Python");
+        assert_eq!(highlighter("rs").unwrap().syntax_name(), "Rust");
+    }
+
+    fn detect(name: &str, lines: &[&str]) -> Option<String> {
+        let syntax_set = SyntaxSet::load_defaults_newlines();
+        let buffer = Buffer {
+            name: Some(name.to_string()),
+            buffer_type: Path::new(name)
+                .extension()
+                .map(|extension| extension.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            ..buffer(lines)
+        };
+        find_syntax(&syntax_set, &buffer).map(|syntax| syntax.name.clone())
+    }
+
+    #[test]
+    fn test_find_syntax_by_file_name() {
+        assert_eq!(detect("project/Makefile", &[]).as_deref(), Some("Makefile"));
+        assert_eq!(
+            detect("/home/me/.bashrc", &[]).as_deref(),
+            Some("Bourne Again Shell (bash)")
+        );
+        assert_eq!(detect("src/main.rs", &[]).as_deref(), Some("Rust"));
+    }
+
+    #[test]
+    fn test_find_syntax_by_shebang() {
+        let python = Some("Python");
+        assert_eq!(
+            detect("script", &["#!/usr/bin/env python3"]).as_deref(),
+            python
+        );
+        assert_eq!(
+            detect("script", &["#!/usr/bin/python3.11 -u"]).as_deref(),
+            python
+        );
+        assert_eq!(
+            detect("script", &["#!/usr/bin/env -S node"]).as_deref(),
+            Some("JavaScript")
+        );
+        assert_eq!(detect("README", &["Nano-rs"]), None);
+    }
+
+    #[test]
+    fn test_find_syntax_by_first_line() {
+        let xml = detect("pom", &["<?xml version=\"1.0\"?>"]);
+        assert_eq!(xml.as_deref(), Some("XML"));
+    }
+
+    #[test]
+    fn test_interpreter() {
+        assert_eq!(interpreter("#!/bin/sh"), Some("sh"));
+        assert_eq!(interpreter("#! /usr/bin/env ruby"), Some("ruby"));
+        assert_eq!(interpreter("#!/usr/bin/env"), None);
+        assert_eq!(interpreter("fn main() {}"), None);
+    }
+
+    #[test]
+    fn test_clip() {
+        let style = Style::default();
+        let spans = vec![(style, 0..3), (style, 3..9), (style, 9..10)];
+        let clipped = clip("日本語 ok", &spans, 1, 4);
+        let text: Vec<&str> = clipped.iter().map(|(_, text)| *text).collect();
+        assert_eq!(text, ["本語", " "]);
+        assert!(clip("abc", &spans, 5, 10).is_empty());
+    }
+}
@@ -0,0 +1,465 @@
+use std::path::Path;
+
+use regex::{Regex, RegexBuilder};
+use syntect::highlighting::Style;
+
+use super::{interpreter, paint, plain, Span};
+use crate::buffer::Buffer;
+use crate::colour::Colour;
+
+/// Syntax
+/// A syntax read from a GNU nano `.nanorc` file: the files it applies to and
+/// the regular expressions it colours.
+///
+/// Rules are applied in order, a later rule painting over the earlier ones
+/// like in nano. `start=`/`end=` rules may span lines, so whether each of
+/// them is open at the end of a line is carried over to the next one in a
+/// `State`.
+#[derive(Debug, Clone)]
+pub struct Syntax {
+    pub name: String,
+    /// The patterns of the file names the syntax applies to
+    files: Vec<Regex>,
+    /// The patterns of the first lines the syntax applies to
+    headers: Vec<Regex>,
+    /// The patterns of the file descriptions the syntax applies to
+    magics: Vec<Regex>,
+    rules: Vec<Rule>,
+    /// The number of `start=`/`end=` rules
+    regions: usize,
+}
+
+/// State
+/// Whether each `start=`/`end=` rule of a syntax is open at the start of a
+/// line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct State {
+    open: Vec<bool>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    colour: Colour,
+    pattern: Pattern,
+}
+
+#[derive(Debug, Clone)]
+enum Pattern {
+    /// A pattern matched within a line
+    Single(Regex),
+    /// A region from `start` to `end`, possibly spanning lines, whose state
+    /// is at index `region`
+    Region {
+        start: Regex,
+        end: Regex,
+        region: usize,
+    },
+}
+
+/// An argument of a nanorc command, `start="..."` being keyed by `start`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Argument {
+    key: Option<String>,
+    value: String,
+}
+
+impl Syntax {
+    /// Get the state at the start of a file
+    pub fn initial_state(&self) -> State {
+        State {
+            open: vec![false; self.regions],
+        }
+    }
+
+    /// Highlight a line, updating the state for the next one
+    /// Text that no rule matches gets the default style.
+    pub fn highlight_line(&self, line: &str, state: &mut State, default: Style) -> Vec<Span> {
+        let mut spans = plain(default, line.len());
+        let mut fill = |from: usize, to: usize, colour: Colour| {
+            paint(&mut spans, colour.style(default), from..to);
+        };
+
+        for rule in &self.rules {
+            match &rule.pattern {
+                Pattern::Single(regex) => regex
+                    .find_iter(line)
+                    .for_each(|found| fill(found.start(), found.end(), rule.colour)),
+                Pattern::Region { start, end, region } => {
+                    let mut from = 0;
+                    if state.open[*region] {
+                        let Some(found) = end.find(line) else {
+                            fill(0, line.len(), rule.colour);
+                            continue;
+                        };
+                        fill(0, found.end(), rule.colour);
+                        state.open[*region] = false;
+                        from = found.end();
+                    }
+
+                    while let Some(opening) = start.find_at(line, from) {
+                        let Some(closing) = end.find_at(line, opening.end()) else {
+                            fill(opening.start(), line.len(), rule.colour);
+                            state.open[*region] = true;
+                            break;
+                        };
+                        fill(opening.start(), closing.end(), rule.colour);
+
+                        // Empty matches must not match again at the same place
+                        from = closing.end();
+                        if from == opening.start() {
+                            match line[from..].chars().next() {
+                                Some(c) => from += c.len_utf8(),
+                                None => break,
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        spans
+    }
+}
+
+/// Parse the syntaxes of a nanorc file
+/// Commands other than `syntax`, `header`, `magic`, `color` and `icolor`
+/// (`comment`, `linter`, ...) do not affect highlighting and are skipped.
+pub fn parse(contents: &str) -> Result<Vec<Syntax>, String> {
+    let mut syntaxes: Vec<Syntax> = Vec::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        parse_command(&mut syntaxes, command, rest)
+            .map_err(|e| format!("line {}: {}", index + 1, e))?;
+    }
+
+    Ok(syntaxes)
+}
+
+/// Parse a command of a nanorc file into the syntaxes
+fn parse_command(syntaxes: &mut Vec<Syntax>, command: &str, rest: &str) -> Result<(), String> {
+    let mut arguments = arguments(rest)?.into_iter();
+
+    if command == "syntax" {
+        let name = arguments.next().ok_or("missing syntax name")?.value;
+        syntaxes.push(Syntax {
+            name,
+            files: arguments
+                .map(|argument| regex(&argument.value, false))
+                .collect::<Result<_, _>>()?,
+            headers: Vec::new(),
+            magics: Vec::new(),
+            rules: Vec::new(),
+            regions: 0,
+        });
+        return Ok(());
+    }
+
+    let syntax = match command {
+        "header" | "magic" | "color" | "icolor" => syntaxes
+            .last_mut()
+            .ok_or_else(|| format!("{} without a syntax", command))?,
+        _ => {
+            log::debug!("Skipping nanorc command {}", command);
+            return Ok(());
+        }
+    };
+
+    match command {
+        "header" => {
+            for argument in arguments {
+                syntax.headers.push(regex(&argument.value, false)?);
+            }
+        }
+        "magic" => {
+            for argument in arguments {
+                syntax.magics.push(regex(&argument.value, true)?);
+            }
+        }
+        _ => {
+            let ignore_case = command == "icolor";
+            let colour = Colour::parse(&arguments.next().ok_or("missing colour")?.value)?;
+            let mut arguments = arguments.peekable();
+            if arguments.peek().is_none() {
+                return Err("missing regex".to_string());
+            }
+
+            while let Some(argument) = arguments.next() {
+                let pattern = match argument.key.as_deref() {
+                    None => Pattern::Single(regex(&argument.value, ignore_case)?),
+                    Some("start") => {
+                        let end = arguments
+                            .next()
+                            .filter(|end| end.key.as_deref() == Some("end"))
+                            .ok_or("start= without end=")?;
+                        syntax.regions += 1;
+                        Pattern::Region {
+                            start: regex(&argument.value, ignore_case)?,
+                            end: regex(&end.value, ignore_case)?,
+                            region: syntax.regions - 1,
+                        }
+                    }
+                    Some(key) => return Err(format!("unexpected {}=", key)),
+                };
+                syntax.rules.push(Rule { colour, pattern });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Split the arguments of a command
+/// A quoted argument ends at a quote followed by a space or the end of the
+/// line, so it may contain quotes like in nano: `"a"b"` is `a"b`.
+fn arguments(text: &str) -> Result<Vec<Argument>, String> {
+    let mut arguments = Vec::new();
+    let mut rest = text.trim_start();
+
+    while !rest.is_empty() {
+        let mut key = None;
+        for prefix in ["start=", "end="] {
+            if let Some(stripped) = rest.strip_prefix(prefix) {
+                key = Some(prefix.trim_end_matches('=').to_string());
+                rest = stripped;
+            }
+        }
+
+        let value;
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted
+                .match_indices('"')
+                .map(|(index, _)| index)
+                .find(|index| {
+                    quoted[index + 1..]
+                        .chars()
+                        .next()
+                        .is_none_or(char::is_whitespace)
+                })
+                .ok_or("unterminated string")?;
+            value = &quoted[..end];
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            value = &rest[..end];
+            rest = &rest[end..];
+        }
+
+        arguments.push(Argument {
+            key,
+            value: value.to_string(),
+        });
+        rest = rest.trim_start();
+    }
+
+    Ok(arguments)
+}
+
+/// Compile a nanorc regular expression
+/// nano uses POSIX extended expressions with the GNU word boundaries `\<`
+/// and `\>`, which are translated to `\b`.
+fn regex(pattern: &str, ignore_case: bool) -> Result<Regex, String> {
+    let mut translated = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            translated.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('<' | '>') => translated.push_str("\\b"),
+            Some(next) => {
+                translated.push(c);
+                translated.push(next);
+            }
+            None => translated.push(c),
+        }
+    }
+
+    RegexBuilder::new(&translated)
+        .case_insensitive(ignore_case)
+        .build()
+        .map_err(|e| e.to_string())
+}
+
+/// Describe a script like libmagic does, from the interpreter of its
+/// shebang line
+/// This is what `magic` patterns are matched against, in place of libmagic,
+/// which is not available. Only scripts are recognised.
+fn describe(first_line: &str) -> Option<String> {
+    let description = match interpreter(first_line)? {
+        "sh" => "POSIX shell",
+        "bash" => "Bourne-Again shell",
+        program => program,
+    };
+    Some(format!("{} script, ASCII text executable", description))
+}
+
+/// Find the syntax of a buffer
+/// The syntax is looked up by the file name patterns first, then by the
+/// header patterns against the first line, and last by the `magic` patterns
+/// against the description of the file, ignoring case.
+pub fn find_syntax(syntaxes: &[Syntax], buffer: &Buffer) -> Option<usize> {
+    let file_name = buffer.name.as_deref().map(Path::new);
+    let first_line = buffer.row(0).map_or("", |content| content.text.as_str());
+
+    file_name
+        .and_then(|name| {
+            let name = name.to_string_lossy();
+            syntaxes
+                .iter()
+                .position(|syntax| syntax.files.iter().any(|file| file.is_match(&name)))
+        })
+        .or_else(|| {
+            syntaxes.iter().position(|syntax| {
+                syntax
+                    .headers
+                    .iter()
+                    .any(|header| header.is_match(first_line))
+            })
+        })
+        .or_else(|| {
+            let description = describe(first_line)?;
+            syntaxes.iter().position(|syntax| {
+                syntax
+                    .magics
+                    .iter()
+                    .any(|magic| magic.is_match(&description))
+            })
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use syntect::highlighting::{Color, FontStyle};
+
+    use super::*;
+    use crate::colour::rgb;
+    use crate::content::Data;
+
+    const NANORC: &str = r##"
+## A made up language
+syntax "nanotest" "\.nt$"
+header "^#!.*nanotest"
+magic "nanotest script"
+comment "#"
+color brightblue "\<(if|else)\>"
+icolor bold,red "\<TODO\>"
+color green start="/\*" end="\*/"
+color ,yellow "[[:space:]]+$"
+"##;
+
+    fn syntax() -> Syntax {
+        parse(NANORC).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_parse() {
+        let syntax = syntax();
+        assert_eq!(syntax.name, "nanotest");
+        assert_eq!(syntax.files.len(), 1);
+        assert_eq!(syntax.headers.len(), 1);
+        assert_eq!(syntax.magics.len(), 1);
+        assert_eq!(syntax.rules.len(), 4);
+        assert_eq!(syntax.regions, 1);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("color red \"x\"").unwrap_err(),
+            "line 1: color without a syntax"
+        );
+        assert!(parse("syntax a\ncolor purplish \"x\"")
+            .unwrap_err()
+            .starts_with("line 2: unknown colour"));
+        assert!(parse("syntax a\ncolor red start=\"x\"").is_err());
+        assert!(parse("syntax a\ncolor red \"(\"").is_err());
+    }
+
+    #[test]
+    fn test_arguments() {
+        let arguments = arguments(r#"green start="a"b" end="\*/"  word"#).unwrap();
+        let values: Vec<_> = arguments.iter().map(|a| a.value.as_str()).collect();
+        assert_eq!(values, ["green", "a\"b", "\\*/", "word"]);
+        assert_eq!(arguments[1].key.as_deref(), Some("start"));
+        assert_eq!(arguments[2].key.as_deref(), Some("end"));
+        assert!(super::arguments("\"open").is_err());
+    }
+
+    #[test]
+    fn test_highlight_line() {
+        let syntax = syntax();
+        let mut state = syntax.initial_state();
+        let default = Style::default();
+
+        let spans = syntax.highlight_line("if x todo", &mut state, default);
+        let ranges: Vec<_> = spans.iter().map(|(_, range)| range.clone()).collect();
+        assert_eq!(ranges, [0..2, 2..5, 5..9]);
+        assert_eq!(spans[0].0.foreground, rgb(92, 92, 255));
+        assert_eq!(spans[1].0, default);
+        assert_eq!(spans[2].0.font_style, FontStyle::BOLD);
+    }
+
+    #[test]
+    fn test_highlight_regions() {
+        let syntax = syntax();
+        let mut state = syntax.initial_state();
+        let green = rgb(0, 205, 0);
+
+        let spans = syntax.highlight_line("a /* if", &mut state, Style::default());
+        assert_eq!(spans.last().unwrap(), &(style_with(green), 2..7));
+        assert_eq!(state.open, [true]);
+
+        let spans = syntax.highlight_line("b */ c", &mut state, Style::default());
+        assert_eq!(spans[0], (style_with(green), 0..4));
+        assert_eq!(state.open, [false]);
+    }
+
+    fn style_with(foreground: Color) -> Style {
+        Style {
+            foreground,
+            ..Style::default()
+        }
+    }
+
+    #[test]
+    fn test_find_syntax() {
+        let syntaxes = parse(NANORC).unwrap();
+        let mut buffer = Buffer {
+            name: Some("src/main.nt".to_string()),
+            data: vec![Data::from("x")],
+            ..Default::default()
+        };
+        assert_eq!(find_syntax(&syntaxes, &buffer), Some(0));
+
+        buffer.name = Some("script".to_string());
+        assert_eq!(find_syntax(&syntaxes, &buffer), None);
+        buffer.data = vec![Data::from("#!/usr/bin/nanotest")];
+        assert_eq!(find_syntax(&syntaxes, &buffer), Some(0));
+    }
+
+    #[test]
+    fn test_find_syntax_by_magic() {
+        let syntaxes = parse(
+            "syntax sh\nmagic \"(POSIX|Bourne-Again) shell script.*text\"\n\
+             syntax python\nmagic \"Python script\"",
+        )
+        .unwrap();
+        let mut buffer = Buffer {
+            name: Some("script".to_string()),
+            data: vec![Data::from("#!/usr/bin/env python3")],
+            ..Default::default()
+        };
+        assert_eq!(find_syntax(&syntaxes, &buffer), Some(1));
+        buffer.data = vec![Data::from("#!/bin/bash -e")];
+        assert_eq!(find_syntax(&syntaxes, &buffer), Some(0));
+        buffer.data = vec![Data::from("python")];
+        assert_eq!(find_syntax(&syntaxes, &buffer), None);
+    }
+}
@@ -0,0 +1,530 @@
+use std::fmt;
+use std::ops::Range;
+
+use syntect::highlighting::{Highlighter as ThemeHighlighter, Style, Theme};
+use syntect::parsing::Scope;
+use tree_sitter::{
+    InputEdit, Language, Node, Parser, Point, Query, QueryCursor, StreamingIterator, Tree,
+};
+use tree_sitter_language::LanguageFn;
+
+use super::{paint, plain, Span};
+use crate::buffer::Buffer;
+use crate::content::Data;
+
+/// Grammar
+/// A language tree-sitter can parse, with the query naming its highlights.
+struct Grammar {
+    name: &'static str,
+    extensions: &'static [&'static str],
+    language: LanguageFn,
+    highlights: &'static str,
+}
+
+/// The languages highlighted with tree-sitter
+const GRAMMARS: &[Grammar] = &[
+    Grammar {
+        name: "Rust",
+        extensions: &["rs"],
+        language: tree_sitter_rust::LANGUAGE,
+        highlights: tree_sitter_rust::HIGHLIGHTS_QUERY,
+    },
+    Grammar {
+        name: "TOML",
+        extensions: &["toml"],
+        language: tree_sitter_toml_ng::LANGUAGE,
+        highlights: tree_sitter_toml_ng::HIGHLIGHTS_QUERY,
+    },
+    Grammar {
+        name: "JSON",
+        extensions: &["json"],
+        language: tree_sitter_json::LANGUAGE,
+        highlights: tree_sitter_json::HIGHLIGHTS_QUERY,
+    },
+    Grammar {
+        name: "Python",
+        extensions: &["py", "pyw", "pyi"],
+        language: tree_sitter_python::LANGUAGE,
+        highlights: tree_sitter_python::HIGHLIGHTS_QUERY,
+    },
+    // Only the block structure: inline markup needs a second grammar
+    Grammar {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        language: tree_sitter_md::LANGUAGE,
+        highlights: tree_sitter_md::HIGHLIGHT_QUERY_BLOCK,
+    },
+];
+
+/// The theme scopes of the capture names used by the highlight queries
+/// A capture without a scope of its own uses the scope of its parent name,
+/// `function.method` falling back to `function`.
+const SCOPES: &[(&str, &str)] = &[
+    ("attribute", "entity.other.attribute-name"),
+    ("boolean", "constant.language"),
+    ("comment", "comment"),
+    ("constant", "constant"),
+    ("constant.builtin", "constant.language"),
+    ("constructor", "entity.name.type"),
+    ("embedded", "meta.embedded"),
+    ("escape", "constant.character.escape"),
+    ("function", "entity.name.function"),
+    ("function.builtin", "support.function"),
+    ("function.macro", "entity.name.function.macro"),
+    ("keyword", "keyword"),
+    ("label", "entity.name.label"),
+    ("number", "constant.numeric"),
+    ("operator", "keyword.operator"),
+    ("property", "variable.other.member"),
+    ("punctuation", "punctuation"),
+    ("string", "string"),
+    ("string.escape", "constant.character.escape"),
+    ("string.special", "string.regexp"),
+    ("string.special.key", "entity.name.tag"),
+    ("text.literal", "markup.raw"),
+    ("text.reference", "markup.underline.link"),
+    ("text.title", "markup.heading"),
+    ("text.uri", "markup.underline.link"),
+    ("type", "entity.name.type"),
+    ("type.builtin", "storage.type"),
+    ("variable", "variable"),
+    ("variable.builtin", "variable.language"),
+    ("variable.parameter", "variable.parameter"),
+];
+
+/// Find the grammar of a file extension
+pub fn find_grammar(extension: &str) -> Option<usize> {
+    GRAMMARS
+        .iter()
+        .position(|grammar| grammar.extensions.contains(&extension))
+}
+
+/// Find a grammar by name or file extension
+pub fn find_grammar_by_token(token: &str) -> Option<usize> {
+    GRAMMARS
+        .iter()
+        .position(|grammar| grammar.name.eq_ignore_ascii_case(token))
+        .or_else(|| find_grammar(token))
+}
+
+/// Get the name of a grammar
+pub fn grammar_name(grammar: usize) -> &'static str {
+    GRAMMARS[grammar].name
+}
+
+/// Get the edit of the syntax tree replacing `removed` lines of a buffer
+/// from `row` with `inserted`, before the buffer is edited
+/// Lines are read with a line break between them, as they are parsed, so
+/// that lines added or removed at the end of the buffer also add or remove
+/// the line break before them.
+pub fn line_edit(buffer: &Buffer, row: usize, removed: usize, inserted: &[String]) -> InputEdit {
+    let lens = |lines: &[Data]| lines.iter().map(|line| line.text.len()).collect::<Vec<_>>();
+    let old = lens(&buffer.data);
+    let new = [
+        &old[..row],
+        &inserted.iter().map(String::len).collect::<Vec<_>>(),
+        &old[row + removed..],
+    ]
+    .concat();
+
+    let (start_byte, start_position) = line_start(&old, row).min(line_start(&new, row));
+    let (old_end_byte, old_end_position) = line_start(&old, row + removed);
+    let (new_end_byte, new_end_position) = line_start(&new, row + inserted.len());
+    InputEdit {
+        start_byte,
+        old_end_byte,
+        new_end_byte,
+        start_position,
+        old_end_position,
+        new_end_position,
+    }
+}
+
+/// Get the byte and point at the start of a row of the lines of the given
+/// lengths, or at the end of the last line past it
+fn line_start(lens: &[usize], row: usize) -> (usize, Point) {
+    let mut byte = 0;
+    let mut end = Point::new(0, 0);
+    for (index, len) in lens.iter().enumerate() {
+        if index > 0 {
+            byte += 1;
+        }
+        if index == row {
+            return (byte, Point::new(row, 0));
+        }
+        byte += len;
+        end = Point::new(index, *len);
+    }
+    (byte, end)
+}
+
+/// Syntax
+/// A buffer parsed with a tree-sitter grammar, and the theme styles of its
+/// highlight captures.
+///
+/// The whole buffer is parsed into a syntax tree, which is queried for the
+/// lines to highlight. After an edit, the tree is parsed again reusing the
+/// unchanged parts of the previous one.
+pub struct Syntax {
+    grammar: usize,
+    parser: Parser,
+    query: Query,
+    /// The style of each capture of the query, if the theme has one
+    styles: Vec<Option<Style>>,
+    tree: Option<Tree>,
+    /// Whether the tree was edited since it was parsed
+    edited: bool,
+}
+
+impl fmt::Debug for Syntax {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Syntax")
+            .field("name", &grammar_name(self.grammar))
+            .field("tree", &self.tree)
+            .finish()
+    }
+}
+
+impl Syntax {
+    /// Prepare the grammar and query, coloured by the theme
+    pub fn new(grammar: usize, theme: &Theme) -> Result<Self, String> {
+        let language: Language = GRAMMARS[grammar].language.into();
+        let mut parser = Parser::new();
+        parser.set_language(&language).map_err(|e| e.to_string())?;
+        let query = Query::new(&language, GRAMMARS[grammar].highlights)
+            .map_err(|e| format!("invalid {} query: {}", grammar_name(grammar), e))?;
+
+        let highlighter = ThemeHighlighter::new(theme);
+        let default = highlighter.get_default();
+        let styles = query
+            .capture_names()
+            .iter()
+            .map(|name| match *name {
+                "none" => Some(default),
+                name => scope(name)
+                    .map(|scope| highlighter.style_for_stack(&[scope]))
+                    .filter(|style| *style != default),
+            })
+            .collect();
+
+        Ok(Self {
+            grammar,
+            parser,
+            query,
+            styles,
+            tree: None,
+            edited: false,
+        })
+    }
+
+    /// Record an edit of the buffer, so that the next parse only re-parses
+    /// the part of the tree it changed
+    pub fn edit(&mut self, edit: &InputEdit) {
+        if let Some(tree) = &mut self.tree {
+            tree.edit(edit);
+            self.edited = true;
+        }
+    }
+
+    /// Parse the buffer again after an edit
+    /// This will return the first line whose nodes the edit changed, which
+    /// may come before the edited lines when it closes a comment or string
+    /// opened above them. A failed parse is reported when highlighting.
+    pub fn reparse(&mut self, buffer: &Buffer) -> Option<usize> {
+        if !self.edited {
+            return None;
+        }
+        let old = self.tree.clone()?;
+        self.parse(buffer).ok()?;
+        let tree = self.tree.as_ref()?;
+        old.changed_ranges(tree)
+            .map(|range| range.start_point.row)
+            .min()
+    }
+
+    /// Parse the buffer, unless the tree is up to date with it
+    fn parse(&mut self, buffer: &Buffer) -> Result<(), String> {
+        if self.tree.is_none() || self.edited {
+            let mut read = |_: usize, point: Point| -> &[u8] {
+                match buffer.data.get(point.row) {
+                    Some(line) if point.column < line.text.len() => {
+                        &line.text.as_bytes()[point.column..]
+                    }
+                    Some(_) if point.row + 1 < buffer.len() => b"\n",
+                    _ => &[],
+                }
+            };
+            let tree = self
+                .parser
+                .parse_with_options(&mut read, self.tree.as_ref(), None)
+                .ok_or_else(|| format!("failed to parse {}", grammar_name(self.grammar)))?;
+            self.tree = Some(tree);
+            self.edited = false;
+        }
+        Ok(())
+    }
+
+    /// Highlight the given lines of the buffer, parsing it if needed
+    pub fn highlight_lines(
+        &mut self,
+        buffer: &Buffer,
+        rows: Range<usize>,
+        default: Style,
+    ) -> Result<Vec<Vec<Span>>, String> {
+        self.parse(buffer)?;
+        let Some(tree) = &self.tree else {
+            return Ok(Vec::new());
+        };
+
+        let mut lines: Vec<Vec<Span>> = buffer.data[rows.clone()]
+            .iter()
+            .map(|line| plain(default, line.text.len()))
+            .collect();
+
+        let mut cursor = QueryCursor::new();
+        cursor.set_point_range(Point::new(rows.start, 0)..Point::new(rows.end, 0));
+        let mut captures = cursor.captures(&self.query, tree.root_node(), |node: Node| {
+            node_text(buffer, node)
+        });
+
+        // A node captured by several patterns takes the first one, and a
+        // node inside another one is painted over it
+        let mut last = None;
+        while let Some((found, index)) = captures.next() {
+            let capture = found.captures[*index];
+            if last == Some(capture.node.id()) {
+                continue;
+            }
+            last = Some(capture.node.id());
+            let Some(style) = self.styles[capture.index as usize] else {
+                continue;
+            };
+
+            let start = capture.node.start_position();
+            let end = capture.node.end_position();
+            for row in start.row.max(rows.start)..(end.row + 1).min(rows.end) {
+                let len = buffer.data[row].text.len();
+                let from = if row == start.row { start.column } else { 0 };
+                let to = if row == end.row { end.column } else { len };
+                paint(
+                    &mut lines[row - rows.start],
+                    style,
+                    from.min(len)..to.min(len),
+                );
+            }
+        }
+
+        Ok(lines)
+    }
+}
+
+/// Get the theme scope of a capture name
+fn scope(name: &str) -> Option<Scope> {
+    let mut name = name;
+    loop {
+        if let Some((_, scope)) = SCOPES.iter().find(|(capture, _)| *capture == name) {
+            return Scope::new(scope).ok();
+        }
+        name = name.rsplit_once('.')?.0;
+    }
+}
+
+/// Get the text of a node, for the predicates of the query
+fn node_text<'a>(buffer: &'a Buffer, node: Node) -> impl Iterator<Item = &'a [u8]> {
+    let start = node.start_position();
+    let end = node.end_position();
+
+    (start.row..=end.row).flat_map(move |row| {
+        let line = buffer
+            .data
+            .get(row)
+            .map_or(&[][..], |line| line.text.as_bytes());
+        let from = if row == start.row { start.column } else { 0 };
+        let to = if row == end.row {
+            end.column
+        } else {
+            line.len()
+        };
+        let text = &line[from.min(line.len())..to.min(line.len())];
+        let newline: &[u8] = if row == end.row { b"" } else { b"\n" };
+        [text, newline]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use syntect::highlighting::ThemeSet;
+
+    use super::*;
+    use crate::content::Data;
+
+    fn theme() -> Theme {
+        ThemeSet::load_defaults().themes["base16-mocha.dark"].clone()
+    }
+
+    fn buffer(lines: &[&str]) -> Buffer {
+        Buffer {
+            data: lines.iter().map(|line| Data::from(*line)).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn highlight(extension: &str, lines: &[&str]) -> Vec<Vec<Span>> {
+        let buffer = buffer(lines);
+        let default = ThemeHighlighter::new(&theme()).get_default();
+        let mut syntax = Syntax::new(find_grammar(extension).unwrap(), &theme()).unwrap();
+        syntax
+            .highlight_lines(&buffer, 0..buffer.len(), default)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_grammars_load() {
+        for grammar in 0..GRAMMARS.len() {
+            assert!(
+                Syntax::new(grammar, &theme()).is_ok(),
+                "{}",
+                grammar_name(grammar)
+            );
+        }
+    }
+
+    #[test]
+    fn test_find_grammar() {
+        assert_eq!(find_grammar("toml").map(grammar_name), Some("TOML"));
+        assert_eq!(
+            find_grammar_by_token("python").map(grammar_name),
+            Some("Python")
+        );
+        assert_eq!(find_grammar("c"), None);
+    }
+
+    #[test]
+    fn test_highlight_rust() {
+        let lines = highlight("rs", &["fn main() {", "    /* a", "    b */", "}"]);
+        let spans = &lines[0];
+        assert_eq!(spans[0].1, 0..2);
+        assert_ne!(spans[0].0, spans[1].0);
+
+        // The block comment spans both lines in the same style
+        let comment = lines[1].last().unwrap();
+        assert_eq!(comment.1, 4..8);
+        assert_eq!(lines[2][0], (comment.0, 0..8));
+    }
+
+    #[test]
+    fn test_highlight_toml() {
+        let lines = highlight("toml", &["[package]", "name = \"nano\""]);
+        assert!(lines[1].len() > 1);
+        assert_eq!(lines[1].last().unwrap().1, 7..13);
+    }
+
+    #[test]
+    fn test_edit() {
+        let default = ThemeHighlighter::new(&theme()).get_default();
+        let mut buffer = buffer(&["fn main() {", "    let a = 1;", "}"]);
+        let mut syntax = Syntax::new(find_grammar("rs").unwrap(), &theme()).unwrap();
+        syntax.highlight_lines(&buffer, 0..3, default).unwrap();
+
+        // Opening a block comment changes the lines after the edited one
+        let inserted = vec!["    /* let a = 1;".to_string()];
+        syntax.edit(&line_edit(&buffer, 1, 1, &inserted));
+        buffer.replace_lines(1, 1, inserted);
+        let lines = syntax.highlight_lines(&buffer, 0..3, default).unwrap();
+        assert_eq!(
+            lines,
+            highlight("rs", &["fn main() {", "    /* let a = 1;", "}"])
+        );
+    }
+
+    #[test]
+    fn test_line_edit() {
+        let buffer = buffer(&["ab", "cde", "f"]);
+        let edit = line_edit(&buffer, 1, 1, &["x".to_string(), "yz".to_string()]);
+        assert_eq!(
+            (edit.start_byte, edit.old_end_byte, edit.new_end_byte),
+            (3, 7, 8)
+        );
+        assert_eq!(edit.new_end_position, Point::new(3, 0));
+
+        // The last line is not followed by a line break
+        let edit = line_edit(&buffer, 2, 1, &["fg".to_string()]);
+        assert_eq!(
+            (edit.start_byte, edit.old_end_byte, edit.new_end_byte),
+            (7, 8, 9)
+        );
+        assert_eq!(edit.new_end_position, Point::new(2, 2));
+        // Removing it removes the line break before it
+        let edit = line_edit(&buffer, 2, 1, &[]);
+        assert_eq!(
+            (edit.start_byte, edit.old_end_byte, edit.new_end_byte),
+            (6, 8, 6)
+        );
+        assert_eq!(edit.start_position, Point::new(1, 3));
+    }
+
+    /// Get the kinds and byte ranges of the nodes of a parsed tree
+    fn nodes(syntax: &Syntax) -> Vec<(&'static str, Range<usize>)> {
+        let mut nodes = Vec::new();
+        let mut cursor = syntax.tree.as_ref().unwrap().walk();
+        'walk: loop {
+            nodes.push((cursor.node().kind(), cursor.node().byte_range()));
+            if cursor.goto_first_child() || cursor.goto_next_sibling() {
+                continue;
+            }
+            while cursor.goto_parent() {
+                if cursor.goto_next_sibling() {
+                    continue 'walk;
+                }
+            }
+            return nodes;
+        }
+    }
+
+    #[test]
+    fn test_edit_last_line() {
+        let default = ThemeHighlighter::new(&theme()).get_default();
+        let edits: [(usize, usize, &[&str]); 3] = [
+            (1, 1, &["fn b() { let x = \"s\"; }"]),
+            (2, 0, &["/* c */"]),
+            (1, 2, &[]),
+        ];
+        let mut buffer = buffer(&["fn a() {}", "fn b() {}"]);
+        let mut syntax = Syntax::new(find_grammar("rs").unwrap(), &theme()).unwrap();
+        syntax.highlight_lines(&buffer, 0..2, default).unwrap();
+
+        for (row, removed, inserted) in edits {
+            let inserted = inserted
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>();
+            syntax.edit(&line_edit(&buffer, row, removed, &inserted));
+            buffer.replace_lines(row, removed, inserted);
+            syntax
+                .highlight_lines(&buffer, 0..buffer.len(), default)
+                .unwrap();
+
+            let mut fresh = Syntax::new(find_grammar("rs").unwrap(), &theme()).unwrap();
+            fresh
+                .highlight_lines(&buffer, 0..buffer.len(), default)
+                .unwrap();
+            assert_eq!(nodes(&syntax), nodes(&fresh));
+        }
+    }
+
+    #[test]
+    fn test_node_text() {
+        let buffer = buffer(&["ab", "cd"]);
+        let mut parser = Parser::new();
+        parser
+            .set_language(&tree_sitter_json::LANGUAGE.into())
+            .unwrap();
+        let tree = parser.parse("ab\ncd", None).unwrap();
+
+        let text: Vec<u8> = node_text(&buffer, tree.root_node())
+            .flatten()
+            .copied()
+            .collect();
+        assert_eq!(text, b"ab\ncd");
+    }
+}
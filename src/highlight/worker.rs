@@ -0,0 +1,500 @@
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use syntect::highlighting::{Style, Theme};
+use syntect::parsing::SyntaxSet;
+
+use super::{nanorc, Engine, Highlighter, Span};
+use crate::buffer::Buffer;
+use crate::error::{NanoError, NanoResult};
+
+/// The number of lines highlighted past the end of the screen
+const LOOKAHEAD: usize = 100;
+
+/// The number of lines highlighted between two checks for requests
+const BATCH: usize = 16;
+
+/// How often the screen is redrawn while visible lines are being highlighted
+pub const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// A request from the editor to the worker
+enum Request {
+    /// Highlight the lines up to, but not including, the given one
+    HighlightTo(usize),
+    /// Set the syntax by name, detecting it with the engine given when no
+    /// syntax has that name
+    SetSyntax(String, Option<Engine>),
+    /// Detect the syntax of the buffer
+    DetectSyntax(Engine),
+    /// Replace `removed` lines from `row` with `inserted`, highlighting again
+    /// from the first line that is not published, at most
+    Edit {
+        row: usize,
+        removed: usize,
+        inserted: Vec<String>,
+        published: usize,
+    },
+}
+
+/// A change of syntax asked for by the editor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxChange {
+    /// The syntax of the given name was set
+    Set(String),
+    /// No syntax has the given name
+    Unknown(String),
+    /// The syntax of the given name was detected for the buffer
+    Detected(String),
+    /// No syntax was detected for the buffer
+    NotDetected,
+}
+
+impl SyntaxChange {
+    /// Get the name of the syntax in use after the change, if it changed
+    fn syntax_name(&self) -> Option<&str> {
+        match self {
+            SyntaxChange::Set(name) | SyntaxChange::Detected(name) => Some(name),
+            SyntaxChange::Unknown(_) | SyntaxChange::NotDetected => None,
+        }
+    }
+}
+
+/// A message from the worker to the editor
+/// `generation` is bumped each time the syntax changes or the buffer is
+/// edited, so that the spans still in the channel are dropped.
+enum Update {
+    /// The spans of the lines from `start` on, or the error that stopped
+    /// highlighting
+    Lines {
+        generation: usize,
+        start: usize,
+        lines: NanoResult<Vec<Vec<Span>>>,
+    },
+    /// The answer to a request to change the syntax
+    Syntax(Vec<SyntaxChange>),
+}
+
+/// Highlight worker
+/// Runs a `Highlighter` on a thread of its own, so that highlighting a huge
+/// file never blocks input. The editor requests the lines it is about to
+/// draw, the worker highlights them (and `LOOKAHEAD` lines past them) in
+/// small batches and publishes their spans, which are collected by `poll`.
+///
+/// Lines whose spans have not arrived yet are drawn unhighlighted. Syntax
+/// changes are answered the same way, so that the editor never waits for a
+/// batch to finish. The highlighter is built on the worker thread, as
+/// syntect's parser state cannot be sent across threads.
+#[derive(Debug)]
+pub struct HighlightWorker {
+    requests: Sender<Request>,
+    updates: Receiver<Update>,
+    syntax_name: String,
+    default_style: Style,
+    generation: usize,
+    /// The spans of the lines published so far
+    spans: Vec<Vec<Span>>,
+    /// The number of lines of the buffer
+    len: usize,
+    /// The end of the lines last drawn
+    visible: usize,
+    /// The end of the lines last requested
+    requested: usize,
+    /// The number of syntax changes not answered yet
+    waiting: usize,
+    /// The syntax changes answered, not taken by the editor yet
+    changes: Vec<SyntaxChange>,
+    failed: bool,
+}
+
+impl HighlightWorker {
+    /// Start highlighting a buffer on a new thread
+    /// Until a syntax is set, lines are highlighted as plain text.
+    pub fn spawn(
+        syntax_set: SyntaxSet,
+        nanorc: Vec<nanorc::Syntax>,
+        theme: Theme,
+        buffer: Buffer,
+    ) -> Self {
+        let (requests, requests_receiver) = mpsc::channel();
+        let (updates_sender, updates) = mpsc::channel();
+        let (ready, started) = mpsc::channel();
+        let len = buffer.len();
+
+        thread::spawn(move || {
+            let highlighter = Highlighter::new(syntax_set, nanorc, theme);
+            let _ = ready.send((
+                highlighter.syntax_name().to_string(),
+                highlighter.default_style(),
+            ));
+            run(highlighter, buffer, requests_receiver, updates_sender);
+        });
+        let (syntax_name, default_style) = started
+            .recv()
+            .expect("the highlighter thread panicked on start");
+
+        Self {
+            requests,
+            updates,
+            syntax_name,
+            default_style,
+            generation: 0,
+            spans: Vec::new(),
+            len,
+            visible: 0,
+            requested: 0,
+            waiting: 0,
+            changes: Vec::new(),
+            failed: false,
+        }
+    }
+
+    /// Get the name of the syntax in use
+    pub fn syntax_name(&self) -> &str {
+        &self.syntax_name
+    }
+
+    /// Get the style of text that is not highlighted
+    pub fn default_style(&self) -> Style {
+        self.default_style
+    }
+
+    /// Highlight with the syntax of the given name or file extension
+    /// When no syntax has that name, the syntax is detected with `fallback`
+    /// if given. This does not wait for the worker to look the syntax up,
+    /// the outcome is collected by `poll`, see `syntax_changes`.
+    pub fn set_syntax(&mut self, name: &str, fallback: Option<Engine>) {
+        self.change_syntax(Request::SetSyntax(name.to_string(), fallback));
+    }
+
+    /// Highlight with the syntax detected for the buffer
+    /// The syntaxes of the given engine are tried first. This does not wait
+    /// for the worker to look the syntax up, see `syntax_changes`.
+    pub fn detect_syntax(&mut self, engine: Engine) {
+        self.change_syntax(Request::DetectSyntax(engine));
+    }
+
+    fn change_syntax(&mut self, request: Request) {
+        // A worker that stopped is reported by `poll`
+        if self.requests.send(request).is_ok() {
+            self.waiting += 1;
+        }
+    }
+
+    /// Take the syntax changes answered since the last call
+    pub fn syntax_changes(&mut self) -> Vec<SyntaxChange> {
+        std::mem::take(&mut self.changes)
+    }
+
+    /// Replace `removed` lines from `row` with `inserted`
+    /// The buffer of the editor has to be edited the same way. The lines
+    /// from the edited one on are highlighted again.
+    pub fn edit(&mut self, row: usize, removed: usize, inserted: Vec<String>) {
+        self.len = self.len - removed + inserted.len();
+        self.generation += 1;
+        self.spans.truncate(row);
+        self.visible = self.visible.min(self.len);
+        self.requested = self.requested.min(self.len);
+        let _ = self.requests.send(Request::Edit {
+            row,
+            removed,
+            inserted,
+            published: self.spans.len(),
+        });
+    }
+
+    /// Request the lines up to, but not including, `end` to be highlighted
+    /// This does not wait for them, see `poll`.
+    pub fn highlight_to(&mut self, end: usize) {
+        self.visible = end.min(self.len);
+        let end = (end + LOOKAHEAD).min(self.len);
+        if end > self.requested {
+            self.requested = end;
+            // A worker that stopped is reported by `poll`
+            let _ = self.requests.send(Request::HighlightTo(end));
+        }
+    }
+
+    /// Collect the spans published by the worker
+    /// This will return whether any line was highlighted, or the error that
+    /// stopped highlighting.
+    pub fn poll(&mut self) -> NanoResult<bool> {
+        let mut updated = false;
+        loop {
+            let update = match self.updates.try_recv() {
+                Ok(update) => update,
+                Err(TryRecvError::Empty) => return Ok(updated),
+                Err(TryRecvError::Disconnected) if self.failed => return Ok(updated),
+                Err(TryRecvError::Disconnected) => {
+                    self.failed = true;
+                    self.waiting = 0;
+                    return Err(NanoError::Generic("Highlighting stopped".to_string()));
+                }
+            };
+
+            match update {
+                Update::Syntax(changes) => {
+                    self.waiting -= 1;
+                    if let Some(name) = changes.iter().find_map(SyntaxChange::syntax_name) {
+                        self.syntax_name = name.to_string();
+                        self.generation += 1;
+                        self.spans.clear();
+                        self.failed = false;
+                        updated = true;
+                    }
+                    self.changes.extend(changes);
+                }
+                Update::Lines { generation, .. } if generation != self.generation => {}
+                Update::Lines {
+                    start,
+                    lines: Ok(lines),
+                    ..
+                } => {
+                    updated |= !lines.is_empty();
+                    self.spans.truncate(start);
+                    self.spans.extend(lines);
+                }
+                Update::Lines { lines: Err(e), .. } => {
+                    self.failed = true;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Get whether lines on screen are still waiting to be highlighted, or
+    /// a syntax change to be answered
+    pub fn is_pending(&self) -> bool {
+        self.waiting > 0 || (!self.failed && self.spans.len() < self.visible)
+    }
+
+    /// Get the spans of a highlighted line
+    pub fn spans(&self, row: usize) -> Option<&[Span]> {
+        self.spans.get(row).map(Vec::as_slice)
+    }
+}
+
+/// Serve the requests of the editor until it goes away
+fn run(
+    mut highlighter: Highlighter,
+    mut buffer: Buffer,
+    requests: Receiver<Request>,
+    updates: Sender<Update>,
+) {
+    let mut generation = 0;
+    let mut target = 0;
+    let mut failed = false;
+
+    loop {
+        let busy = !failed && highlighter.highlighted() < target;
+        let request = if busy {
+            match requests.try_recv() {
+                Ok(request) => Some(request),
+                Err(TryRecvError::Empty) => None,
+                Err(TryRecvError::Disconnected) => return,
+            }
+        } else {
+            match requests.recv() {
+                Ok(request) => Some(request),
+                Err(_) => return,
+            }
+        };
+
+        let detect = |highlighter: &mut Highlighter, engine| {
+            if highlighter.detect_syntax(&buffer, engine) {
+                SyntaxChange::Detected(highlighter.syntax_name().to_string())
+            } else {
+                SyntaxChange::NotDetected
+            }
+        };
+        let changes = match request {
+            Some(Request::HighlightTo(end)) => {
+                target = target.max(end);
+                continue;
+            }
+            Some(Request::SetSyntax(name, fallback)) => {
+                if highlighter.set_syntax(&name) {
+                    vec![SyntaxChange::Set(highlighter.syntax_name().to_string())]
+                } else {
+                    let mut changes = vec![SyntaxChange::Unknown(name)];
+                    changes.extend(fallback.map(|engine| detect(&mut highlighter, engine)));
+                    changes
+                }
+            }
+            Some(Request::DetectSyntax(engine)) => vec![detect(&mut highlighter, engine)],
+            Some(Request::Edit {
+                row,
+                removed,
+                inserted,
+                published,
+            }) => {
+                highlighter.edit(&mut buffer, row, removed, inserted);
+                highlighter.invalidate(published);
+                generation += 1;
+                target = target.min(buffer.len());
+                continue;
+            }
+            None => {
+                let start = highlighter.highlighted();
+                let end = (start + BATCH).min(target);
+                let lines = highlighter.highlight_to(&buffer, end);
+                failed = lines.is_err();
+                let update = Update::Lines {
+                    generation,
+                    start,
+                    lines,
+                };
+                if updates.send(update).is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+
+        if changes.iter().any(|change| change.syntax_name().is_some()) {
+            generation += 1;
+            failed = false;
+        }
+        if updates.send(Update::Syntax(changes)).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use syntect::highlighting::ThemeSet;
+
+    use super::*;
+    use crate::content::Data;
+
+    fn worker(lines: usize) -> HighlightWorker {
+        let theme = ThemeSet::load_defaults().themes["base16-mocha.dark"].clone();
+        let buffer = Buffer {
+            data: vec![Data::from("fn main() {}"); lines],
+            ..Default::default()
+        };
+        let mut worker = HighlightWorker::spawn(
+            SyntaxSet::load_defaults_newlines(),
+            Vec::new(),
+            theme,
+            buffer,
+        );
+        worker.set_syntax("rs", None);
+        assert_eq!(answers(&mut worker), [SyntaxChange::Set("Rust".into())]);
+        worker
+    }
+
+    /// Poll the worker until the syntax changes asked for are answered
+    fn answers(worker: &mut HighlightWorker) -> Vec<SyntaxChange> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while worker.waiting > 0 {
+            assert!(Instant::now() < deadline, "syntax change timed out");
+            worker.poll().unwrap();
+            thread::sleep(POLL_INTERVAL);
+        }
+        worker.syntax_changes()
+    }
+
+    /// Poll the worker until the visible lines are highlighted
+    fn wait(worker: &mut HighlightWorker) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while worker.is_pending() {
+            assert!(Instant::now() < deadline, "highlighting timed out");
+            worker.poll().unwrap();
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    #[test]
+    fn test_highlights_ahead_of_screen() {
+        let mut worker = worker(500);
+        assert!(worker.spans(0).is_none());
+
+        worker.highlight_to(10);
+        assert!(worker.is_pending());
+        wait(&mut worker);
+        assert!(worker.spans(9).is_some());
+
+        // The lookahead is highlighted too, but no further
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while worker.spans(10 + LOOKAHEAD - 1).is_none() {
+            assert!(Instant::now() < deadline, "highlighting timed out");
+            worker.poll().unwrap();
+            thread::sleep(POLL_INTERVAL);
+        }
+        assert!(worker.spans(10 + LOOKAHEAD).is_none());
+    }
+
+    #[test]
+    fn test_set_syntax_restarts() {
+        let mut worker = worker(20);
+        worker.highlight_to(20);
+        wait(&mut worker);
+        let rust = worker.spans(0).unwrap().to_vec();
+
+        worker.set_syntax("not-a-language", None);
+        assert_eq!(
+            answers(&mut worker),
+            [SyntaxChange::Unknown("not-a-language".into())]
+        );
+        assert_eq!(worker.syntax_name(), "Rust");
+        worker.set_syntax("txt", None);
+        assert_eq!(
+            answers(&mut worker),
+            [SyntaxChange::Set("Plain Text".into())]
+        );
+        assert_eq!(worker.syntax_name(), "Plain Text");
+
+        wait(&mut worker);
+        assert_ne!(worker.spans(0).unwrap(), rust);
+    }
+
+    #[test]
+    fn test_unknown_syntax_falls_back_to_detection() {
+        let mut worker = worker(1);
+        worker.set_syntax("not-a-language", Some(Engine::Syntect));
+        let changes = answers(&mut worker);
+        assert_eq!(changes[0], SyntaxChange::Unknown("not-a-language".into()));
+        assert_eq!(changes.len(), 2);
+    }
+
+    #[test]
+    fn test_large_buffer_stays_responsive() {
+        let mut worker = worker(200_000);
+        worker.highlight_to(200_000);
+
+        // Nothing waits for the worker, busy as it is with the whole buffer
+        let start = Instant::now();
+        worker.set_syntax("txt", None);
+        worker.edit(0, 1, vec!["fn edited() {}".to_string()]);
+        worker.highlight_to(10);
+        assert!(start.elapsed() < Duration::from_millis(50));
+
+        assert_eq!(
+            answers(&mut worker),
+            [SyntaxChange::Set("Plain Text".into())]
+        );
+        wait(&mut worker);
+        assert!(worker.spans(9).is_some());
+    }
+
+    #[test]
+    fn test_edit_highlights_again() {
+        let mut worker = worker(20);
+        worker.highlight_to(20);
+        wait(&mut worker);
+        let code = worker.spans(5).unwrap().to_vec();
+
+        // Opening a block comment changes the lines after the edited one
+        worker.edit(4, 1, vec!["/*".to_string(), "fn main() {}".to_string()]);
+        assert!(worker.spans(4).is_none());
+        worker.highlight_to(21);
+        wait(&mut worker);
+        assert_eq!(worker.spans(3).unwrap(), code);
+        assert_ne!(worker.spans(5).unwrap(), code);
+        assert!(worker.spans(20).is_some());
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::configuration::NanoConfiguration;
+use crate::error::NanoResult;
+
+/// The maximum number of entries kept for each kind of history
+pub const MAX_HISTORY_LENGTH: usize = 100;
+
+/// History kind
+/// The prompts that remember what was typed in them. nano's replace and
+/// execute histories are added along with their prompts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HistoryKind {
+    /// Strings searched for
+    Search,
+}
+
+impl HistoryKind {
+    /// Every kind of history, in the order they are stored
+    pub const ALL: [HistoryKind; 1] = [HistoryKind::Search];
+
+    /// The name of the file the history is stored in
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            HistoryKind::Search => "search_history",
+        }
+    }
+}
+
+/// History
+/// The prompt history, persisted between sessions in the data directory
+/// (nano's `historylog`). Entries are stored oldest first, one per line.
+#[derive(Debug, Clone, Default)]
+pub struct History {
+    directory: Option<PathBuf>,
+    entries: HashMap<HistoryKind, Vec<String>>,
+}
+
+impl History {
+    /// Load the history from the data directory
+    /// A history that cannot be read is logged and started afresh, it should
+    /// never prevent the editor from starting.
+    pub fn load() -> Self {
+        match NanoConfiguration::data_dir() {
+            Some(directory) => History::load_from(directory),
+            None => History::default(),
+        }
+    }
+
+    /// Load the history from the given directory
+    pub fn load_from<P: AsRef<Path>>(directory: P) -> Self {
+        let directory = directory.as_ref().to_path_buf();
+        let mut history = History {
+            directory: Some(directory.clone()),
+            entries: HashMap::new(),
+        };
+
+        for kind in HistoryKind::ALL {
+            let path = directory.join(kind.file_name());
+            match fs::read_to_string(&path) {
+                Ok(contents) => contents.lines().for_each(|line| history.add(kind, line)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => log::warn!("Failed to read {}: {}", path.display(), e),
+            }
+        }
+
+        history
+    }
+
+    /// Save the history to the directory it was loaded from
+    pub fn save(&self) -> NanoResult<()> {
+        let Some(directory) = &self.directory else {
+            return Ok(());
+        };
+        fs::create_dir_all(directory)?;
+
+        for kind in HistoryKind::ALL {
+            let mut contents = self.entries(kind).join("\n");
+            contents.push('\n');
+            fs::write(directory.join(kind.file_name()), contents)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the entries of a history, oldest first
+    pub fn entries(&self, kind: HistoryKind) -> &[String] {
+        self.entries.get(&kind).map_or(&[], Vec::as_slice)
+    }
+
+    /// Add an entry to a history
+    /// An entry that is already present is moved to the end instead of being
+    /// duplicated, and the oldest entries are dropped past
+    /// `MAX_HISTORY_LENGTH`.
+    pub fn add(&mut self, kind: HistoryKind, entry: &str) {
+        if entry.is_empty() {
+            return;
+        }
+
+        let entries = self.entries.entry(kind).or_default();
+        entries.retain(|existing| existing != entry);
+        entries.push(entry.to_string());
+
+        if entries.len() > MAX_HISTORY_LENGTH {
+            entries.drain(..entries.len() - MAX_HISTORY_LENGTH);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("nano-rs-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        directory
+    }
+
+    #[test]
+    fn test_add_deduplicates() {
+        let mut history = History::default();
+        history.add(HistoryKind::Search, "foo");
+        history.add(HistoryKind::Search, "bar");
+        history.add(HistoryKind::Search, "foo");
+        assert_eq!(history.entries(HistoryKind::Search), ["bar", "foo"]);
+    }
+
+    #[test]
+    fn test_add_ignores_empty_entries() {
+        let mut history = History::default();
+        history.add(HistoryKind::Search, "");
+        assert!(history.entries(HistoryKind::Search).is_empty());
+    }
+
+    #[test]
+    fn test_add_caps_length() {
+        let mut history = History::default();
+        for index in 0..MAX_HISTORY_LENGTH + 10 {
+            history.add(HistoryKind::Search, &index.to_string());
+        }
+        let entries = history.entries(HistoryKind::Search);
+        assert_eq!(entries.len(), MAX_HISTORY_LENGTH);
+        assert_eq!(entries[0], "10");
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let directory = test_directory("history");
+        let mut history = History::load_from(&directory);
+        history.add(HistoryKind::Search, "needle");
+        history.save().unwrap();
+        assert!(!directory.join("replace_history").exists());
+
+        let history = History::load_from(&directory);
+        assert_eq!(history.entries(HistoryKind::Search), ["needle"]);
+
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -1,8 +1,22 @@
+mod arguments;
 mod buffer;
+mod colour;
 mod config;
 pub mod content;
+mod cursor;
 mod error;
+mod gutter;
+mod highlight;
+mod history;
+mod message;
+mod position_log;
+mod prompt;
+mod screen;
+mod shortcut;
+mod undo;
 mod view;
+mod whitespace;
+mod wrap;
 use std::fs::File;
 use std::io::Write;
 pub mod editor;
@@ -0,0 +1,183 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use crossterm::style::StyledContent;
+
+use crate::colour::Palette;
+use crate::content;
+
+/// How long a message stays in the status bar without a keypress
+pub const MESSAGE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Severity
+/// How important a message is, which decides its colour.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// Message
+/// A message shown in the status bar, e.g. "Search Wrapped".
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub text: String,
+    pub severity: Severity,
+    /// When the message was first shown
+    shown: Instant,
+}
+
+impl Message {
+    /// Render the message for a terminal of the given width
+    /// The message is centred between brackets like in nano, and coloured by
+    /// severity.
+    pub fn render(&self, width: u16, palette: &Palette) -> Vec<StyledContent<String>> {
+        let width = width as usize;
+        let text = format!("[ {} ]", self.text);
+        let (text, text_width) = content::fit(&text, width);
+        let padding = (width - text_width) / 2;
+
+        let colour = match self.severity {
+            Severity::Info => palette.status,
+            Severity::Warning => palette.warning,
+            Severity::Error => palette.error,
+        };
+
+        vec![
+            StyledContent::new(Default::default(), " ".repeat(padding)),
+            palette.paint(colour, text),
+        ]
+    }
+}
+
+/// Message queue
+/// Messages posted by any part of the editor, shown one at a time in the
+/// status bar. The current message is cleared by a keypress or after
+/// `MESSAGE_TIMEOUT`, whichever comes first.
+#[derive(Debug, Clone, Default)]
+pub struct MessageQueue {
+    messages: VecDeque<Message>,
+}
+
+impl MessageQueue {
+    /// Post a message
+    pub fn post<S: Into<String>>(&mut self, severity: Severity, text: S) {
+        let text = text.into();
+        match severity {
+            Severity::Info => log::info!("{}", text),
+            Severity::Warning => log::warn!("{}", text),
+            Severity::Error => log::error!("{}", text),
+        }
+
+        self.messages.push_back(Message {
+            text,
+            severity,
+            shown: Instant::now(),
+        });
+    }
+
+    /// Post an informational message
+    pub fn info<S: Into<String>>(&mut self, text: S) {
+        self.post(Severity::Info, text);
+    }
+
+    /// Post a warning
+    pub fn warning<S: Into<String>>(&mut self, text: S) {
+        self.post(Severity::Warning, text);
+    }
+
+    /// Post an error
+    pub fn error<S: Into<String>>(&mut self, text: S) {
+        self.post(Severity::Error, text);
+    }
+
+    /// Get the message to show
+    pub fn current(&self) -> Option<&Message> {
+        self.messages.front()
+    }
+
+    /// Clear the current message, showing the next one
+    pub fn dismiss(&mut self) {
+        self.messages.pop_front();
+        if let Some(next) = self.messages.front_mut() {
+            next.shown = Instant::now();
+        }
+    }
+
+    /// Clear the current message if it has been shown for too long
+    /// Returns whether a message was cleared.
+    pub fn expire(&mut self, now: Instant) -> bool {
+        match self.time_left(now) {
+            Some(left) if left.is_zero() => {
+                self.dismiss();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Get how long the current message is left to be shown
+    pub fn time_left(&self, now: Instant) -> Option<Duration> {
+        self.current()
+            .map(|message| MESSAGE_TIMEOUT.saturating_sub(now.duration_since(message.shown)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syntect::highlighting::Theme;
+
+    use super::*;
+    use crate::colour::ColourDepth;
+
+    #[test]
+    fn test_messages_are_shown_in_order() {
+        let mut messages = MessageQueue::default();
+        messages.info("Search Wrapped");
+        messages.error("Invalid line or column number");
+
+        assert_eq!(messages.current().unwrap().text, "Search Wrapped");
+        messages.dismiss();
+        let current = messages.current().unwrap();
+        assert_eq!(current.severity, Severity::Error);
+        messages.dismiss();
+        assert!(messages.current().is_none());
+    }
+
+    #[test]
+    fn test_messages_expire() {
+        let mut messages = MessageQueue::default();
+        messages.warning("Syntax not found");
+        let now = Instant::now();
+
+        assert!(!messages.expire(now));
+        assert!(messages.time_left(now).unwrap() <= MESSAGE_TIMEOUT);
+        assert!(messages.expire(now + MESSAGE_TIMEOUT));
+        assert!(messages.current().is_none());
+        assert_eq!(messages.time_left(now), None);
+    }
+
+    #[test]
+    fn test_render_centres_message() {
+        let mut messages = MessageQueue::default();
+        messages.info("abc");
+        let palette = Palette::from_theme(&Theme::default(), ColourDepth::Monochrome);
+        let line: String = messages
+            .current()
+            .unwrap()
+            .render(17, &palette)
+            .iter()
+            .map(|text| text.content().as_str())
+            .collect();
+        assert!(line.starts_with("     "));
+        assert!(!line.starts_with("      "));
+        assert!(line.contains("[ abc ]"));
+
+        // Wide characters take two columns each
+        let mut messages = MessageQueue::default();
+        messages.info("日本");
+        let line = messages.current().unwrap().render(17, &palette);
+        assert_eq!(line[0].content(), "    ");
+    }
+}
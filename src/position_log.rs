@@ -0,0 +1,162 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::configuration::NanoConfiguration;
+use crate::error::NanoResult;
+
+/// The maximum number of files whose position is remembered
+pub const MAX_POSITION_LOG_LENGTH: usize = 200;
+
+/// The name of the file the positions are stored in
+const POSITION_LOG_FILE: &str = "filepos_history";
+
+/// File position
+/// The last cursor position in a file, one-based like nano.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePosition {
+    pub path: PathBuf,
+    pub line: usize,
+    pub column: usize,
+}
+
+/// Position log
+/// The last cursor position of each opened file, persisted between sessions
+/// in the data directory (nano's `positionlog`). Each line of the file holds
+/// a path followed by a line and a column, the most recent entry last.
+#[derive(Debug, Clone, Default)]
+pub struct PositionLog {
+    file: Option<PathBuf>,
+    entries: Vec<FilePosition>,
+}
+
+impl PositionLog {
+    /// Load the position log from the data directory
+    /// A log that cannot be read is logged and started afresh, it should
+    /// never prevent the editor from starting.
+    pub fn load() -> Self {
+        match NanoConfiguration::data_dir() {
+            Some(directory) => PositionLog::load_from(directory.join(POSITION_LOG_FILE)),
+            None => PositionLog::default(),
+        }
+    }
+
+    /// Load the position log from the given file
+    pub fn load_from<P: AsRef<Path>>(file: P) -> Self {
+        let file = file.as_ref().to_path_buf();
+        let mut log = PositionLog {
+            file: Some(file.clone()),
+            entries: Vec::new(),
+        };
+
+        match fs::read_to_string(&file) {
+            Ok(contents) => contents
+                .lines()
+                .filter_map(parse_entry)
+                .for_each(|entry| log.record(&entry.path, entry.line, entry.column)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => log::warn!("Failed to read {}: {}", file.display(), e),
+        }
+
+        log
+    }
+
+    /// Save the position log to the file it was loaded from
+    pub fn save(&self) -> NanoResult<()> {
+        let Some(file) = &self.file else {
+            return Ok(());
+        };
+        if let Some(directory) = file.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        let contents: String = self
+            .entries
+            .iter()
+            .map(|entry| format!("{} {} {}\n", entry.path.display(), entry.line, entry.column))
+            .collect();
+        fs::write(file, contents)?;
+
+        Ok(())
+    }
+
+    /// Get the last known `(line, column)` in a file
+    pub fn position<P: AsRef<Path>>(&self, path: P) -> Option<(usize, usize)> {
+        self.entries
+            .iter()
+            .find(|entry| entry.path == path.as_ref())
+            .map(|entry| (entry.line, entry.column))
+    }
+
+    /// Record the position in a file
+    /// The file becomes the most recent entry, and the oldest entries are
+    /// dropped past `MAX_POSITION_LOG_LENGTH`.
+    pub fn record<P: AsRef<Path>>(&mut self, path: P, line: usize, column: usize) {
+        let path = path.as_ref().to_path_buf();
+        self.entries.retain(|entry| entry.path != path);
+        self.entries.push(FilePosition { path, line, column });
+
+        if self.entries.len() > MAX_POSITION_LOG_LENGTH {
+            self.entries
+                .drain(..self.entries.len() - MAX_POSITION_LOG_LENGTH);
+        }
+    }
+}
+
+/// Parse a `path line column` entry, the path possibly containing spaces
+fn parse_entry(line: &str) -> Option<FilePosition> {
+    let mut parts = line.rsplitn(3, ' ');
+    let column = parts.next()?.parse().ok()?;
+    let line = parts.next()?.parse().ok()?;
+    let path = PathBuf::from(parts.next()?);
+
+    Some(FilePosition { path, line, column })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_and_position() {
+        let mut log = PositionLog::default();
+        log.record("/tmp/a.rs", 3, 4);
+        log.record("/tmp/b.rs", 1, 1);
+        log.record("/tmp/a.rs", 10, 2);
+        assert_eq!(log.position("/tmp/a.rs"), Some((10, 2)));
+        assert_eq!(log.entries.len(), 2);
+        assert_eq!(log.position("/tmp/c.rs"), None);
+    }
+
+    #[test]
+    fn test_record_caps_length() {
+        let mut log = PositionLog::default();
+        for index in 0..MAX_POSITION_LOG_LENGTH + 1 {
+            log.record(format!("/tmp/{}", index), 1, 1);
+        }
+        assert_eq!(log.entries.len(), MAX_POSITION_LOG_LENGTH);
+        assert_eq!(log.position("/tmp/0"), None);
+    }
+
+    #[test]
+    fn test_parse_entry_with_spaces() {
+        let entry = parse_entry("/tmp/my file.txt 12 5").unwrap();
+        assert_eq!(entry.path, PathBuf::from("/tmp/my file.txt"));
+        assert_eq!((entry.line, entry.column), (12, 5));
+        assert_eq!(parse_entry("/tmp/broken 12"), None);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let file = std::env::temp_dir()
+            .join(format!("nano-rs-positions-{}", std::process::id()))
+            .join(POSITION_LOG_FILE);
+        let mut log = PositionLog::load_from(&file);
+        log.record("/tmp/a.rs", 7, 3);
+        log.save().unwrap();
+
+        let log = PositionLog::load_from(&file);
+        assert_eq!(log.position("/tmp/a.rs"), Some((7, 3)));
+
+        fs::remove_dir_all(file.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,396 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::style::StyledContent;
+
+use crate::colour::Palette;
+use crate::content::{self, UnicodeSegmentation};
+use crate::shortcut::Mode;
+
+/// Prompt action
+/// The outcome of handing a key to the prompt.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptAction {
+    /// The key was consumed by the prompt, keep prompting
+    Continue,
+    /// The user confirmed the input with Enter
+    Submit(String),
+    /// The user cancelled the prompt with Esc or Ctrl+C
+    Cancel,
+    /// The key is not a line editing key, it is up to the owner of the prompt
+    /// to handle it, e.g. as one of the shortcuts of the prompt
+    Unhandled(KeyEvent),
+}
+
+/// Prompt
+/// A single line of input at the bottom of the screen, used by every feature
+/// that needs to ask the user something (search, write out, go to line, ...).
+///
+/// The cursor is stored as a grapheme index into the input.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    /// The mode of the prompt, which decides the shortcuts shown
+    pub mode: Mode,
+    /// The question shown before the input
+    pub message: String,
+    input: String,
+    cursor: usize,
+    /// Earlier entries of this prompt, oldest first
+    history: Vec<String>,
+    /// The history entry shown, `history.len()` while typing a new entry
+    history_index: usize,
+    /// The input typed before browsing the history
+    draft: String,
+}
+
+impl Prompt {
+    /// Create a new, empty prompt
+    pub fn new<S: Into<String>>(mode: Mode, message: S) -> Self {
+        Self {
+            mode,
+            message: message.into(),
+            input: String::new(),
+            cursor: 0,
+            history: Vec::new(),
+            history_index: 0,
+            draft: String::new(),
+        }
+    }
+
+    /// Attach a history to the prompt, browsable with Up and Down
+    pub fn with_history(mut self, history: &[String]) -> Self {
+        self.history = history.to_vec();
+        self.history_index = self.history.len();
+        self
+    }
+
+    /// Replace the input, moving the cursor to its end
+    pub fn set_input<S: Into<String>>(&mut self, input: S) {
+        self.input = input.into();
+        self.cursor = self.len();
+    }
+
+    /// Handle a key event
+    /// This will apply line editing keys to the input, and report whether the
+    /// prompt was submitted or cancelled.
+    pub fn handle_key(&mut self, event: KeyEvent) -> PromptAction {
+        let control = event.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = event.modifiers.contains(KeyModifiers::ALT);
+
+        match event.code {
+            KeyCode::Enter => return PromptAction::Submit(self.input.clone()),
+            KeyCode::Esc => return PromptAction::Cancel,
+            KeyCode::Char('c') if control => return PromptAction::Cancel,
+
+            KeyCode::Up => self.previous_entry(),
+            KeyCode::Down => self.next_entry(),
+
+            KeyCode::Left if control => self.cursor = self.previous_word(),
+            KeyCode::Right if control => self.cursor = self.next_word(),
+            KeyCode::Left => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Char('b') if control => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Right => self.cursor = (self.cursor + 1).min(self.len()),
+            KeyCode::Char('f') if control => self.cursor = (self.cursor + 1).min(self.len()),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::Char('a') if control => self.cursor = 0,
+            KeyCode::End => self.cursor = self.len(),
+            KeyCode::Char('e') if control => self.cursor = self.len(),
+
+            KeyCode::Backspace if control || alt => self.delete(self.previous_word(), self.cursor),
+            KeyCode::Delete if control => self.delete(self.cursor, self.next_word()),
+            KeyCode::Char('d') if alt => self.delete(self.cursor, self.next_word()),
+            KeyCode::Backspace => self.delete(self.cursor.saturating_sub(1), self.cursor),
+            KeyCode::Char('h') if control => {
+                self.delete(self.cursor.saturating_sub(1), self.cursor)
+            }
+            KeyCode::Delete => self.delete(self.cursor, self.cursor + 1),
+            KeyCode::Char('d') if control => self.delete(self.cursor, self.cursor + 1),
+            KeyCode::Char('k') if control => self.delete(0, self.len()),
+
+            KeyCode::Char(c) if !control && !alt => self.insert(&c.to_string()),
+            _ => return PromptAction::Unhandled(event),
+        }
+
+        PromptAction::Continue
+    }
+
+    /// Insert pasted text at the cursor
+    /// Line breaks are dropped, as the prompt is a single line.
+    pub fn paste(&mut self, text: &str) {
+        let text: String = text.chars().filter(|c| !matches!(c, '\n' | '\r')).collect();
+        self.insert(&text);
+    }
+
+    /// Render the prompt for a terminal of the given width
+    /// Returns the rendered line and the column of the cursor. When the input
+    /// is too long, it is scrolled so that the cursor stays visible. Both are
+    /// measured in display columns, wide graphemes taking two.
+    pub fn render(&self, width: u16, palette: &Palette) -> (StyledContent<String>, u16) {
+        let width = width as usize;
+        let message = format!("{}: ", self.message);
+        let (message, message_width) = content::fit(&message, width);
+        let available = width - message_width;
+
+        // Keep as much of the input before the cursor as fits with the
+        // cursor itself
+        let graphemes: Vec<(usize, &str)> = self.input.grapheme_indices(true).collect();
+        let mut start = self.cursor;
+        let mut before = 0;
+        while start > 0 && before + content::width(graphemes[start - 1].1) < available {
+            start -= 1;
+            before += content::width(graphemes[start].1);
+        }
+        let offset = graphemes
+            .get(start)
+            .map_or(self.input.len(), |(offset, _)| *offset);
+        let (input, input_width) = content::fit(&self.input[offset..], available);
+
+        let cursor = (message_width + before).min(width.saturating_sub(1));
+        let line = format!(
+            "{}{}{}",
+            message,
+            input,
+            " ".repeat(available - input_width)
+        );
+
+        (palette.paint(palette.prompt, &line), cursor as u16)
+    }
+
+    /// Show the previous history entry, keeping what was typed as a draft
+    fn previous_entry(&mut self) {
+        if self.history_index == 0 {
+            return;
+        }
+        if self.history_index == self.history.len() {
+            self.draft = self.input.clone();
+        }
+        self.history_index -= 1;
+        self.set_input(self.history[self.history_index].clone());
+    }
+
+    /// Show the next history entry, or the draft past the newest entry
+    fn next_entry(&mut self) {
+        if self.history_index >= self.history.len() {
+            return;
+        }
+        self.history_index += 1;
+        match self.history.get(self.history_index) {
+            Some(entry) => self.set_input(entry.clone()),
+            None => self.set_input(self.draft.clone()),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.input.graphemes(true).count()
+    }
+
+    fn insert(&mut self, text: &str) {
+        let offset = self.byte_offset(self.cursor);
+        self.input.insert_str(offset, text);
+        self.cursor += text.graphemes(true).count();
+    }
+
+    /// Delete the graphemes in `start..end`, leaving the cursor at `start`
+    fn delete(&mut self, start: usize, end: usize) {
+        let end = end.min(self.len());
+        if start >= end {
+            return;
+        }
+        let range = self.byte_offset(start)..self.byte_offset(end);
+        self.input.replace_range(range, "");
+        self.cursor = start;
+    }
+
+    fn byte_offset(&self, index: usize) -> usize {
+        self.input
+            .grapheme_indices(true)
+            .nth(index)
+            .map_or(self.input.len(), |(offset, _)| offset)
+    }
+
+    /// The start of the word before the cursor
+    fn previous_word(&self) -> usize {
+        let graphemes = self.input.graphemes(true).collect::<Vec<_>>();
+        let mut index = self.cursor;
+        while index > 0 && !is_word(graphemes[index - 1]) {
+            index -= 1;
+        }
+        while index > 0 && is_word(graphemes[index - 1]) {
+            index -= 1;
+        }
+        index
+    }
+
+    /// The end of the word after the cursor
+    fn next_word(&self) -> usize {
+        let graphemes = self.input.graphemes(true).collect::<Vec<_>>();
+        let mut index = self.cursor;
+        while index < graphemes.len() && !is_word(graphemes[index]) {
+            index += 1;
+        }
+        while index < graphemes.len() && is_word(graphemes[index]) {
+            index += 1;
+        }
+        index
+    }
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.chars().any(|c| c.is_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use syntect::highlighting::Theme;
+
+    use super::*;
+    use crate::colour::ColourDepth;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn ctrl(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::CONTROL)
+    }
+
+    fn prompt_with(input: &str) -> Prompt {
+        let mut prompt = Prompt::new(Mode::GotoLine, "Enter line number");
+        prompt.set_input(input);
+        prompt
+    }
+
+    #[test]
+    fn test_typing() {
+        let mut prompt = prompt_with("");
+        prompt.handle_key(key(KeyCode::Char('4')));
+        prompt.handle_key(key(KeyCode::Char('2')));
+        assert_eq!(prompt.input, "42");
+        assert_eq!(prompt.cursor, 2);
+    }
+
+    #[test]
+    fn test_insert_in_the_middle() {
+        let mut prompt = prompt_with("hllo");
+        prompt.handle_key(key(KeyCode::Home));
+        prompt.handle_key(key(KeyCode::Right));
+        prompt.handle_key(key(KeyCode::Char('e')));
+        assert_eq!(prompt.input, "hello");
+        assert_eq!(prompt.cursor, 2);
+    }
+
+    #[test]
+    fn test_cursor_stays_in_bounds() {
+        let mut prompt = prompt_with("ab");
+        prompt.handle_key(key(KeyCode::Right));
+        assert_eq!(prompt.cursor, 2);
+        prompt.handle_key(key(KeyCode::Home));
+        prompt.handle_key(key(KeyCode::Left));
+        assert_eq!(prompt.cursor, 0);
+    }
+
+    #[test]
+    fn test_backspace_and_delete() {
+        let mut prompt = prompt_with("日本語");
+        prompt.handle_key(key(KeyCode::Backspace));
+        assert_eq!(prompt.input, "日本");
+        prompt.handle_key(key(KeyCode::Home));
+        prompt.handle_key(key(KeyCode::Delete));
+        assert_eq!(prompt.input, "本");
+        assert_eq!(prompt.cursor, 0);
+    }
+
+    #[test]
+    fn test_word_deletion() {
+        let mut prompt = prompt_with("fn main() {");
+        prompt.handle_key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::ALT));
+        assert_eq!(prompt.input, "fn ");
+        prompt.handle_key(key(KeyCode::Home));
+        prompt.handle_key(ctrl(KeyCode::Delete));
+        assert_eq!(prompt.input, " ");
+    }
+
+    #[test]
+    fn test_word_movement() {
+        let mut prompt = prompt_with("one two three");
+        prompt.handle_key(ctrl(KeyCode::Left));
+        assert_eq!(prompt.cursor, 8);
+        prompt.handle_key(ctrl(KeyCode::Left));
+        assert_eq!(prompt.cursor, 4);
+        prompt.handle_key(ctrl(KeyCode::Right));
+        assert_eq!(prompt.cursor, 7);
+    }
+
+    #[test]
+    fn test_paste_drops_line_breaks() {
+        let mut prompt = prompt_with("");
+        prompt.paste("foo\r\nbar");
+        assert_eq!(prompt.input, "foobar");
+        assert_eq!(prompt.cursor, 6);
+    }
+
+    #[test]
+    fn test_submit_and_cancel() {
+        let mut prompt = prompt_with("12");
+        assert_eq!(
+            prompt.handle_key(key(KeyCode::Enter)),
+            PromptAction::Submit("12".into())
+        );
+        assert_eq!(prompt.handle_key(key(KeyCode::Esc)), PromptAction::Cancel);
+        assert_eq!(
+            prompt.handle_key(ctrl(KeyCode::Char('c'))),
+            PromptAction::Cancel
+        );
+    }
+
+    #[test]
+    fn test_unhandled_shortcut() {
+        let mut prompt = prompt_with("");
+        let event = ctrl(KeyCode::Char('y'));
+        assert_eq!(prompt.handle_key(event), PromptAction::Unhandled(event));
+    }
+
+    #[test]
+    fn test_history_browsing() {
+        let history = ["first".to_string(), "second".to_string()];
+        let mut prompt = Prompt::new(Mode::Search, "Search").with_history(&history);
+        prompt.paste("draft");
+
+        prompt.handle_key(key(KeyCode::Up));
+        assert_eq!(prompt.input, "second");
+        prompt.handle_key(key(KeyCode::Up));
+        prompt.handle_key(key(KeyCode::Up));
+        assert_eq!(prompt.input, "first");
+        assert_eq!(prompt.cursor, 5);
+
+        prompt.handle_key(key(KeyCode::Down));
+        prompt.handle_key(key(KeyCode::Down));
+        assert_eq!(prompt.input, "draft");
+        prompt.handle_key(key(KeyCode::Down));
+        assert_eq!(prompt.input, "draft");
+    }
+
+    #[test]
+    fn test_render_scrolls_long_input() {
+        let prompt = prompt_with("0123456789");
+        let palette = Palette::from_theme(&Theme::default(), ColourDepth::Monochrome);
+        let (_, cursor) = prompt.render(10, &palette);
+        assert_eq!(cursor, 9);
+
+        let mut prompt = prompt_with("12");
+        prompt.handle_key(key(KeyCode::Home));
+        let (_, cursor) = prompt.render(80, &palette);
+        assert_eq!(cursor, "Enter line number: ".len() as u16);
+    }
+
+    #[test]
+    fn test_render_wide_input() {
+        let palette = Palette::from_theme(&Theme::default(), ColourDepth::Monochrome);
+        let prompt = prompt_with("日本語");
+        let (_, cursor) = prompt.render(80, &palette);
+        assert_eq!(cursor, "Enter line number: ".len() as u16 + 6);
+
+        // Scrolled by whole graphemes, keeping the cursor on screen
+        let (line, cursor) = prompt.render(24, &palette);
+        assert_eq!(cursor, 23);
+        assert_eq!(line.content(), "Enter line number: 本語 ");
+    }
+}
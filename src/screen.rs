@@ -0,0 +1,356 @@
+use std::io::{self, Write};
+
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::style::{Attribute, ContentStyle, SetAttribute, SetStyle, StyledContent};
+use crossterm::terminal::{Clear, ClearType};
+use crossterm::{queue, QueueableCommand};
+
+use crate::content::{self, UnicodeSegmentation};
+use crate::error::NanoResult;
+use crate::terminal::Position;
+
+/// Cell
+/// A single column of a row of the screen: the grapheme drawn in it and its
+/// style.
+///
+/// A wide grapheme is drawn in the first of its two cells, the second being
+/// left with an empty symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cell {
+    pub symbol: String,
+    pub style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            symbol: String::from(" "),
+            style: ContentStyle::default(),
+        }
+    }
+}
+
+/// Screen
+/// The contents of the whole terminal as a grid of styled cells.
+///
+/// A frame is drawn into the grid, then `flush` compares it with the frame
+/// shown before and writes only the cells that changed, in a single write.
+/// The first frame, and the one after a `resize`, is drawn in full.
+#[derive(Debug, Clone)]
+pub struct Screen {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    /// The frame shown on the terminal, unknown before the first one
+    previous: Option<Vec<Cell>>,
+    /// Where the cursor is shown, hidden when `None`
+    cursor: Option<Position>,
+}
+
+impl Screen {
+    /// Create an empty screen of the given size
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            cells: vec![Cell::default(); width as usize * height as usize],
+            previous: None,
+            cursor: None,
+        }
+    }
+
+    /// Resize the screen to a terminal of the given size
+    /// What the terminal shows after a resize is unknown, so the next frame
+    /// is drawn in full.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        *self = Screen::new(width, height);
+    }
+
+    /// Blank the frame being drawn
+    pub fn clear(&mut self) {
+        self.cells.fill(Cell::default());
+        self.cursor = None;
+    }
+
+    /// Draw styled text from a position, clipped to the end of its row
+    /// Returns the column following the text.
+    pub fn print(&mut self, x: u16, y: u16, text: &[StyledContent<String>]) -> u16 {
+        let mut x = x;
+        for content in text {
+            x = self.print_str(x, y, content.content(), *content.style());
+        }
+        x
+    }
+
+    /// Draw text in a single style, clipped to the end of its row
+    /// A wide grapheme not fitting in the last column is drawn as a blank.
+    /// Returns the column following the text.
+    pub fn print_str(&mut self, x: u16, y: u16, text: &str, style: ContentStyle) -> u16 {
+        let mut x = x;
+        for grapheme in text.graphemes(true) {
+            if x >= self.width || y >= self.height {
+                break;
+            }
+            let width = content::width(grapheme) as u16;
+            if x + width > self.width {
+                self.put(x, y, " ", style, 1);
+                return self.width;
+            }
+            self.put(x, y, grapheme, style, width);
+            x += width;
+        }
+        x
+    }
+
+    /// Draw a grapheme in the cells from a position
+    /// The wide graphemes it is drawn over part of are blanked, as half of
+    /// one cannot be shown.
+    fn put(&mut self, x: u16, y: u16, grapheme: &str, style: ContentStyle, width: u16) {
+        for column in x..x + width {
+            self.split_wide(column, y);
+        }
+        if let Some(cell) = self.cell_mut(x, y) {
+            *cell = Cell {
+                symbol: grapheme.to_string(),
+                style,
+            };
+        }
+        if width == 2 {
+            if let Some(cell) = self.cell_mut(x + 1, y) {
+                *cell = Cell {
+                    symbol: String::new(),
+                    style,
+                };
+            }
+        }
+    }
+
+    /// Blank the wide grapheme covering a cell, if any
+    fn split_wide(&mut self, x: u16, y: u16) {
+        let Some(index) = self.index(x, y) else {
+            return;
+        };
+        let start = if self.cells[index].symbol.is_empty() {
+            index - 1
+        } else if self.is_wide(x, y) {
+            index
+        } else {
+            return;
+        };
+        for cell in &mut self.cells[start..start + 2] {
+            cell.symbol = String::from(" ");
+        }
+    }
+
+    /// Check whether the cell at a position holds a wide grapheme
+    fn is_wide(&self, x: u16, y: u16) -> bool {
+        self.index(x + 1, y)
+            .is_some_and(|index| self.cells[index].symbol.is_empty())
+    }
+
+    /// Show the cursor at a position of the screen
+    pub fn set_cursor(&mut self, position: Position) {
+        self.cursor = Some(position);
+    }
+
+    fn cell_mut(&mut self, x: u16, y: u16) -> Option<&mut Cell> {
+        self.index(x, y).map(|index| &mut self.cells[index])
+    }
+
+    fn index(&self, x: u16, y: u16) -> Option<usize> {
+        (x < self.width && y < self.height).then(|| y as usize * self.width as usize + x as usize)
+    }
+
+    /// Write the changes of the frame to the terminal
+    pub fn flush(&mut self) -> NanoResult<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&self.diff()?)?;
+        stdout.flush()?;
+
+        self.previous = Some(self.cells.clone());
+        Ok(())
+    }
+
+    /// Get the commands drawing the frame over the one shown before
+    /// The cursor is hidden while cells are drawn, so that it does not jump
+    /// around the screen.
+    fn diff(&self) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new();
+        out.queue(Hide)?;
+
+        let blank = vec![Cell::default(); self.cells.len()];
+        let previous = match &self.previous {
+            Some(previous) => previous,
+            None => {
+                queue!(out, SetAttribute(Attribute::Reset), Clear(ClearType::All))?;
+                &blank
+            }
+        };
+
+        // Where the terminal cursor is, after the last cell drawn
+        let mut position = None;
+        let mut style = ContentStyle::default();
+        for (index, cell) in self.cells.iter().enumerate() {
+            let x = (index % self.width as usize) as u16;
+            let y = (index / self.width as usize) as u16;
+            // The second cell of a wide grapheme is drawn with the first
+            let width = if self.is_wide(x, y) { 2 } else { 1 };
+            if cell.symbol.is_empty()
+                || self.cells[index..index + width] == previous[index..index + width]
+            {
+                continue;
+            }
+
+            if position != Some((x, y)) {
+                out.queue(MoveTo(x, y))?;
+            }
+            if cell.style != style {
+                queue!(out, SetAttribute(Attribute::Reset), SetStyle(cell.style))?;
+                style = cell.style;
+            }
+            out.write_all(cell.symbol.as_bytes())?;
+
+            // The terminal cursor stays on the last column of a row
+            let next = x + width as u16;
+            position = (next < self.width).then_some((next, y));
+        }
+
+        out.queue(SetAttribute(Attribute::Reset))?;
+        if let Some(cursor) = self.cursor {
+            queue!(out, MoveTo(cursor.x, cursor.y), Show)?;
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crossterm::style::{Color, Stylize};
+    use syntect::highlighting::Style;
+
+    use super::*;
+    use crate::colour::ColourDepth;
+    use crate::highlight::nanorc;
+
+    fn cell(screen: &Screen, x: u16, y: u16) -> &Cell {
+        &screen.cells[screen.index(x, y).unwrap()]
+    }
+
+    fn text(screen: &Screen, y: u16) -> String {
+        (0..screen.width)
+            .map(|x| cell(screen, x, y).symbol.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_print_clips_to_row() {
+        let mut screen = Screen::new(5, 2);
+        let bold = ContentStyle::new().bold();
+        assert_eq!(screen.print_str(3, 0, "abc", bold), 5);
+        assert_eq!(text(&screen, 0), "   ab");
+        assert_eq!(text(&screen, 1), "     ");
+        assert_eq!(cell(&screen, 3, 0).style, bold);
+    }
+
+    #[test]
+    fn test_print_styled() {
+        let mut screen = Screen::new(6, 1);
+        let x = screen.print(0, 0, &["ab".to_string().red(), "c".to_string().on_blue()]);
+        assert_eq!(x, 3);
+        assert_eq!(text(&screen, 0), "abc   ");
+        assert_eq!(cell(&screen, 0, 0).style.foreground_color, Some(Color::Red));
+        assert_eq!(
+            cell(&screen, 2, 0).style.background_color,
+            Some(Color::Blue)
+        );
+        assert_eq!(cell(&screen, 3, 0).style, ContentStyle::default());
+    }
+
+    #[test]
+    fn test_print_nanorc_styles() {
+        let syntax = nanorc::parse("syntax a\ncolor bold,red \"if\"\ncolor ,yellow \" +$\"")
+            .unwrap()
+            .remove(0);
+        let default = Style::default();
+        let line = "if  ";
+        let spans = syntax.highlight_line(line, &mut syntax.initial_state(), default);
+
+        let mut screen = Screen::new(4, 1);
+        let mut x = 0;
+        for (style, range) in spans {
+            let style = ColourDepth::Ansi16.syntax_style(style, default);
+            x = screen.print_str(x, 0, &line[range], style);
+        }
+        let style = cell(&screen, 0, 0).style;
+        assert_eq!(style.foreground_color, Some(Color::DarkRed));
+        assert!(style.attributes.has(Attribute::Bold));
+        let style = cell(&screen, 2, 0).style;
+        assert_eq!(style.background_color, Some(Color::DarkYellow));
+        assert!(!style.attributes.has(Attribute::Bold));
+    }
+
+    #[test]
+    fn test_diff_only_changed_cells() {
+        let mut screen = Screen::new(4, 2);
+        screen.print_str(0, 0, "ab", ContentStyle::default());
+        let full = screen.diff().unwrap();
+        assert!(String::from_utf8_lossy(&full).contains("\x1b[2J"));
+        screen.previous = Some(screen.cells.clone());
+
+        screen.clear();
+        screen.print_str(0, 0, "ax", ContentStyle::default());
+        screen.set_cursor(Position { x: 2, y: 1 });
+        let diff = String::from_utf8(screen.diff().unwrap()).unwrap();
+        assert_eq!(diff, "\x1b[?25l\x1b[1;2Hx\x1b[0m\x1b[2;3H\x1b[?25h");
+    }
+
+    #[test]
+    fn test_print_wide() {
+        let mut screen = Screen::new(5, 1);
+        assert_eq!(screen.print_str(0, 0, "日本語", ContentStyle::default()), 5);
+        assert_eq!(text(&screen, 0), "日本 ");
+        assert_eq!(cell(&screen, 1, 0).symbol, "");
+
+        // Drawing over half of a wide grapheme blanks the other half
+        screen.print_str(1, 0, "a", ContentStyle::default());
+        screen.print_str(2, 0, "👨\u{200d}👩\u{200d}👧", ContentStyle::default());
+        assert_eq!(text(&screen, 0), " a👨\u{200d}👩\u{200d}👧 ");
+    }
+
+    #[test]
+    fn test_diff_wide() {
+        let mut screen = Screen::new(4, 1);
+        screen.print_str(0, 0, "a日b", ContentStyle::default());
+        screen.previous = Some(screen.cells.clone());
+
+        screen.clear();
+        screen.print_str(0, 0, "a日c", ContentStyle::default());
+        let diff = String::from_utf8(screen.diff().unwrap()).unwrap();
+        assert_eq!(diff, "\x1b[?25l\x1b[1;4Hc\x1b[0m");
+
+        // The wide grapheme is drawn again when its second cell changes
+        screen.previous = Some(screen.cells.clone());
+        screen.clear();
+        screen.print_str(0, 0, "a本c", ContentStyle::default());
+        let diff = String::from_utf8(screen.diff().unwrap()).unwrap();
+        assert_eq!(diff, "\x1b[?25l\x1b[1;2H本\x1b[0m");
+    }
+
+    #[test]
+    fn test_resize_redraws() {
+        let mut screen = Screen::new(4, 2);
+        screen.previous = Some(screen.cells.clone());
+        screen.resize(6, 3);
+        assert_eq!(text(&screen, 2), "      ");
+        let diff = String::from_utf8(screen.diff().unwrap()).unwrap();
+        assert!(diff.contains("\x1b[2J"));
+    }
+
+    #[test]
+    fn test_diff_unchanged_frame() {
+        let mut screen = Screen::new(4, 2);
+        screen.previous = Some(screen.cells.clone());
+        let diff = String::from_utf8(screen.diff().unwrap()).unwrap();
+        assert_eq!(diff, "\x1b[?25l\x1b[0m");
+    }
+}
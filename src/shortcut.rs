@@ -0,0 +1,215 @@
+use crossterm::style::StyledContent;
+
+use crate::colour::Palette;
+use crate::content;
+
+/// Shortcut
+/// A single entry of the shortcut bar, e.g. `^X Exit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shortcut {
+    /// The key combination, in nano notation (`^X`, `M-U`)
+    pub key: &'static str,
+    /// A short description of what the key does
+    pub label: &'static str,
+}
+
+const fn shortcut(key: &'static str, label: &'static str) -> Shortcut {
+    Shortcut { key, label }
+}
+
+const EDIT_SHORTCUTS: &[Shortcut] = &[
+    shortcut("^G", "Help"),
+    shortcut("^X", "Exit"),
+    shortcut("^O", "Write Out"),
+    shortcut("^R", "Read File"),
+    shortcut("^W", "Where Is"),
+    shortcut("^\\", "Replace"),
+    shortcut("^K", "Cut"),
+    shortcut("^U", "Paste"),
+    shortcut("^T", "Execute"),
+    shortcut("^J", "Justify"),
+    shortcut("^C", "Location"),
+    shortcut("^/", "Go To Line"),
+    shortcut("M-U", "Undo"),
+    shortcut("M-E", "Redo"),
+    shortcut("M-A", "Set Mark"),
+    shortcut("M-6", "Copy"),
+    shortcut("M-Y", "Syntax"),
+];
+
+const SEARCH_SHORTCUTS: &[Shortcut] = &[
+    shortcut("^C", "Cancel"),
+    shortcut("^Y", "First Line"),
+    shortcut("^V", "Last Line"),
+    shortcut("^T", "Go To Line"),
+];
+
+const GOTO_LINE_SHORTCUTS: &[Shortcut] = &[
+    shortcut("^C", "Cancel"),
+    shortcut("^Y", "First Line"),
+    shortcut("^V", "Last Line"),
+];
+
+const SYNTAX_SHORTCUTS: &[Shortcut] = &[shortcut("^C", "Cancel")];
+
+/// Mode
+/// The context the editor is currently in. Each mode has its own set of
+/// shortcuts shown at the bottom of the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// Editing the buffer
+    #[default]
+    Edit,
+    /// Prompting for a search string
+    Search,
+    /// Prompting for a line and column number
+    GotoLine,
+    /// Prompting for the syntax to highlight with
+    Syntax,
+}
+
+impl Mode {
+    /// Get the shortcuts available in this mode, in display order
+    pub fn shortcuts(&self) -> &'static [Shortcut] {
+        match self {
+            Mode::Edit => EDIT_SHORTCUTS,
+            Mode::Search => SEARCH_SHORTCUTS,
+            Mode::GotoLine => GOTO_LINE_SHORTCUTS,
+            Mode::Syntax => SYNTAX_SHORTCUTS,
+        }
+    }
+}
+
+/// Get the notation of a key pressed with Ctrl or Alt, as shown in the bar
+/// Terminals report Ctrl+\\ as Ctrl+4.
+pub fn notation(c: char, control: bool) -> String {
+    match (c, control) {
+        ('4', true) => String::from("^\\"),
+        (c, true) => format!("^{}", c.to_ascii_uppercase()),
+        (c, false) => format!("M-{}", c.to_ascii_uppercase()),
+    }
+}
+
+/// Shortcut bar
+/// The two rows at the bottom of the screen listing the shortcuts of the
+/// current mode. Shortcuts fill the bar column by column, so the first two
+/// shortcuts share the first column.
+#[derive(Debug, Clone)]
+pub struct ShortcutBar {
+    /// The width of a single column
+    pub column_width: usize,
+    /// The shortcuts shown on each of the two rows
+    pub rows: [Vec<Shortcut>; 2],
+}
+
+impl ShortcutBar {
+    /// Lay out the shortcuts for a terminal of the given width
+    ///
+    /// Like nano, the number of columns grows with the width of the terminal,
+    /// and shortcuts that don't fit are left out.
+    pub fn new(shortcuts: &[Shortcut], width: u16) -> Self {
+        let width = width as usize;
+        let columns = ((width + 40) / 20).min(shortcuts.len().div_ceil(2));
+        let column_width = width.checked_div(columns).unwrap_or_default();
+
+        let mut rows = [Vec::new(), Vec::new()];
+        for (index, shortcut) in shortcuts.iter().take(columns * 2).enumerate() {
+            rows[index % 2].push(*shortcut);
+        }
+
+        Self { column_width, rows }
+    }
+
+    /// Render a row of the bar
+    /// The key is shown in the shortcut colour, followed by its label,
+    /// truncated to the column width.
+    pub fn render_row(&self, row: usize, palette: &Palette) -> Vec<StyledContent<String>> {
+        let mut line = Vec::new();
+
+        for shortcut in &self.rows[row] {
+            let key_width = content::text_width(shortcut.key);
+            if key_width >= self.column_width {
+                break;
+            }
+            let label_width = self.column_width - key_width;
+            let label = format!(" {}", shortcut.label);
+            let (label, shown) = content::fit(&label, label_width);
+
+            line.push(palette.paint(palette.shortcut, shortcut.key));
+            line.push(StyledContent::new(
+                Default::default(),
+                format!("{}{}", label, " ".repeat(label_width - shown)),
+            ));
+        }
+
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use syntect::highlighting::Theme;
+
+    use super::*;
+    use crate::colour::ColourDepth;
+
+    #[test]
+    fn test_edit_mode_is_default() {
+        assert_eq!(Mode::default(), Mode::Edit);
+        assert_eq!(Mode::Edit.shortcuts()[1], shortcut("^X", "Exit"));
+    }
+
+    #[test]
+    fn test_notation() {
+        assert_eq!(notation('o', true), "^O");
+        assert_eq!(notation('4', true), "^\\");
+        assert_eq!(notation('a', false), "M-A");
+        assert_eq!(notation('6', false), "M-6");
+    }
+
+    #[test]
+    fn test_shortcuts_fill_columns() {
+        let bar = ShortcutBar::new(Mode::Edit.shortcuts(), 80);
+        assert_eq!(bar.rows[0][0].label, "Help");
+        assert_eq!(bar.rows[1][0].label, "Exit");
+        assert_eq!(bar.rows[0][1].label, "Write Out");
+    }
+
+    #[test]
+    fn test_columns_adapt_to_width() {
+        let bar = ShortcutBar::new(Mode::Edit.shortcuts(), 80);
+        assert_eq!(bar.rows[0].len(), 6);
+        assert_eq!(bar.column_width, 13);
+
+        let bar = ShortcutBar::new(Mode::Edit.shortcuts(), 40);
+        assert_eq!(bar.rows[0].len(), 4);
+        assert_eq!(bar.column_width, 10);
+    }
+
+    #[test]
+    fn test_columns_limited_by_shortcuts() {
+        let shortcuts = [shortcut("^G", "Help"), shortcut("^C", "Cancel")];
+        let bar = ShortcutBar::new(&shortcuts, 200);
+        assert_eq!(bar.rows[0].len(), 1);
+        assert_eq!(bar.rows[1].len(), 1);
+        assert_eq!(bar.column_width, 200);
+    }
+
+    #[test]
+    fn test_wide_labels_fit_columns() {
+        let shortcuts = [shortcut("^G", "帮助帮助"), shortcut("^C", "Cancel")];
+        let bar = ShortcutBar::new(&shortcuts, 8);
+        let palette = Palette::from_theme(&Theme::default(), ColourDepth::Monochrome);
+        let row = bar.render_row(0, &palette);
+        assert_eq!(row[0].content(), "^G");
+        assert_eq!(row[1].content(), " 帮助 ");
+    }
+
+    #[test]
+    fn test_zero_width() {
+        let bar = ShortcutBar::new(Mode::Edit.shortcuts(), 0);
+        assert_eq!(bar.column_width, 0);
+        let palette = Palette::from_theme(&Theme::default(), ColourDepth::Monochrome);
+        assert!(bar.render_row(0, &palette).is_empty());
+    }
+}
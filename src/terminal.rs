@@ -1,14 +1,24 @@
 use std::fmt::Display;
 use std::io::{self, Write};
+use std::time::{Duration, Instant};
 
 use crossterm::cursor::{self, SetCursorStyle};
-use crossterm::event::{self, EnableMouseCapture, KeyEvent};
+use crossterm::event::{self, EnableBracketedPaste, EnableMouseCapture, Event};
 use crossterm::{terminal as cterminal, Command};
 
 use crate::error::NanoResult;
 
+/// The number of rows above the edit area, used by the title bar
+pub const TITLE_BAR_HEIGHT: u16 = 1;
+
+/// The number of rows below the edit area, used by the prompt
+pub const STATUS_BAR_HEIGHT: u16 = 1;
+
+/// The number of rows below the status bar, used by the shortcut bar
+pub const SHORTCUT_BAR_HEIGHT: u16 = 2;
+
 /// Cursor position
-#[derive(Debug, Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Position {
     /// The x position of the cursor
     pub x: u16,
@@ -28,6 +38,34 @@ impl From<(u16, u16)> for Position {
     }
 }
 
+/// Location
+/// A place in the buffer, as the display column and the line it is shown
+/// at. Unlike a `Position` on the screen, it is not bounded by the size of
+/// the terminal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Location {
+    /// The display column
+    pub x: usize,
+    /// The line of the buffer
+    pub y: usize,
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "(x: {}, y: {})", self.x, self.y)
+    }
+}
+
+/// Scrolling
+/// How the terminal view follows the cursor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Scrolling {
+    /// The number of lines kept in view above and below the cursor
+    pub margin: u16,
+    /// Whether to scroll line by line, rather than by half a screen
+    pub smooth: bool,
+}
+
 /// Terminal view
 ///
 /// This struct is used to store the terminal view state.
@@ -40,12 +78,20 @@ pub struct Terminal {
     /// The height of the terminal view
     pub height: u16,
 
+    /// The number of rows of the terminal, including the bars around the
+    /// terminal view
+    pub rows: u16,
+
     /// The current scroll offset
-    pub offset: Position,
+    pub offset: Location,
+
+    /// The first chunk shown of the line at `offset.y`, when lines are soft
+    /// wrapped
+    pub offset_chunk: usize,
 
     /// The current cursor position, relative to the terminal view
     /// It is a tuple of (x, y) - (column, row)
-    pub cursor: Position,
+    pub cursor: Location,
 }
 
 impl Display for Terminal {
@@ -64,19 +110,148 @@ impl Terminal {
     /// The terminal view is initialized with the current terminal size.
     pub fn new() -> NanoResult<Self> {
         Terminal::init()?;
-        let (width, height) = cterminal::size()?;
-        Ok(Self {
-            width,
-            height: height - 2, // Subtract 2 for the status bar
+        let (width, rows) = cterminal::size()?;
+        let mut terminal = Self {
+            width: 0,
+            height: 0,
+            rows: 0,
+            offset: Location::default(),
+            offset_chunk: 0,
+            cursor: Location::default(),
+        };
+        terminal.resize(width, rows);
+        Ok(terminal)
+    }
+
+    /// Resize the terminal view to a terminal of the given size
+    /// The rows left once the bars are drawn go to the terminal view.
+    pub fn resize(&mut self, width: u16, rows: u16) {
+        self.width = width;
+        self.rows = rows;
+        self.height =
+            rows.saturating_sub(TITLE_BAR_HEIGHT + STATUS_BAR_HEIGHT + SHORTCUT_BAR_HEIGHT);
+    }
+
+    /// Get whether the terminal is too small to show the terminal view
+    pub fn is_too_small(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// Scroll the terminal view so that the cursor is in it
+    /// `lines` is the number of lines of the buffer, and `columns` the
+    /// number of columns the text is shown in.
+    ///
+    /// Only the line of the cursor scrolls horizontally, by pages like in
+    /// nano, so `offset.x` is the first column shown of that line.
+    pub fn scroll_to_cursor(&mut self, lines: usize, columns: u16, scrolling: Scrolling) {
+        if self.height == 0 {
+            return;
+        }
+        let Location { x, y } = self.cursor;
+        let height = self.height as usize;
+        self.offset.x = page_start(x, columns as usize);
+
+        // The margins shrink near the ends of the buffer, and on screens too
+        // small to keep them on both sides
+        let margin = (scrolling.margin as usize).min((height - 1) / 2);
+        let above = margin.min(y);
+        let below = margin.min(lines.saturating_sub(y.saturating_add(1)));
+
+        let top = self.offset.y;
+        let bottom = top.saturating_add(height);
+        if y >= top + above && y.saturating_add(below) < bottom {
+            return;
+        }
 
-            offset: Position::default(),
-            cursor: Position::default(),
-        })
+        self.offset.y = if !scrolling.smooth {
+            y.saturating_sub(height / 2)
+        } else if y < top + above {
+            y - above
+        } else {
+            y.saturating_add(below + 1).saturating_sub(height)
+        };
+    }
+
+    /// Scroll the terminal view so that the cursor is in it, lines being
+    /// soft wrapped
+    /// The view scrolls by rows of the screen rather than by lines, from
+    /// `(offset.y, offset_chunk)`. `chunk` is the chunk of its line the
+    /// cursor is in, and `chunks` gives the number of chunks of a line.
+    pub fn scroll_to_chunk(
+        &mut self,
+        chunk: usize,
+        lines: usize,
+        scrolling: Scrolling,
+        chunks: impl Fn(usize) -> usize,
+    ) {
+        if self.height == 0 {
+            return;
+        }
+        self.offset.x = 0;
+        let height = self.height as usize;
+        let cursor = (self.cursor.y, chunk);
+        let top = (self.offset.y, self.offset_chunk);
+
+        // Walk up to `rows` rows of the screen from a chunk, returning where
+        // the walk stopped and the number of rows walked
+        let previous = |(line, chunk)| match (line, chunk) {
+            (_, 1..) => Some((line, chunk - 1)),
+            (1.., 0) => Some((line - 1, chunks(line - 1).saturating_sub(1))),
+            _ => None,
+        };
+        let next = |(line, chunk)| match chunk + 1 < chunks(line) {
+            true => Some((line, chunk + 1)),
+            false => (line + 1 < lines).then_some((line + 1, 0)),
+        };
+        let walk = |step: &dyn Fn((usize, usize)) -> Option<(usize, usize)>, from, rows| {
+            let mut position = from;
+            for walked in 0..rows {
+                match step(position) {
+                    Some(stepped) => position = stepped,
+                    None => return (position, walked),
+                }
+            }
+            (position, rows)
+        };
+
+        let margin = scrolling.margin.min((self.height - 1) / 2) as usize;
+        let (_, above) = walk(&previous, cursor, margin);
+        let (_, below) = walk(&next, cursor, margin);
+
+        // The row of the screen the cursor is on, if it is in view
+        let mut row = None;
+        let mut position = top;
+        for index in 0..height {
+            if position == cursor {
+                row = Some(index);
+                break;
+            }
+            match next(position) {
+                Some(stepped) => position = stepped,
+                None => break,
+            }
+        }
+
+        let down = match row {
+            Some(row) if row >= above && row + below < height => return,
+            Some(row) => row >= above,
+            None => cursor > top,
+        };
+        let rows = if !scrolling.smooth {
+            height / 2
+        } else if down {
+            height - 1 - below
+        } else {
+            above
+        };
+        let (top, _) = walk(&previous, cursor, rows);
+        self.offset.y = top.0;
+        self.offset_chunk = top.1;
     }
 
     /// Initialize the terminal view
     /// This will enable raw mode and enter the alternate screen
-    /// It will also enable mouse capture
+    /// It will also enable mouse capture and bracketed paste
     /// This should be called before starting the program
     ///
     pub fn init() -> NanoResult<()> {
@@ -84,18 +259,20 @@ impl Terminal {
         cterminal::enable_raw_mode()?;
         Terminal::set_cursor_style(SetCursorStyle::BlinkingBar)?;
         Terminal::execute(EnableMouseCapture)?;
+        Terminal::execute(EnableBracketedPaste)?;
 
         Ok(())
     }
 
     /// Reset the terminal view
     /// This will disable raw mode and leave the alternate screen
-    /// It will also disable mouse capture
+    /// It will also disable mouse capture and bracketed paste
     /// This should be called before exiting the program
     pub fn reset() -> NanoResult<()> {
         Terminal::execute(cterminal::LeaveAlternateScreen)?;
         Terminal::set_cursor_style(SetCursorStyle::SteadyBar)?;
         Terminal::execute(event::DisableMouseCapture)?;
+        Terminal::execute(event::DisableBracketedPaste)?;
         Terminal::show_cursor()?;
         cterminal::disable_raw_mode()?;
 
@@ -110,15 +287,6 @@ impl Terminal {
         Terminal::execute(cterminal::SetTitle(title))
     }
 
-    /// Set the cursor position
-    pub fn set_cursor_position(&mut self, cursor: Position) {
-        self.cursor = cursor;
-
-        let (x, y) = (cursor.x, cursor.y);
-
-        Terminal::execute(cursor::MoveTo(x, y)).expect("Failed to move cursor");
-    }
-
     pub fn set_cursor_style(cursor_style: SetCursorStyle) -> NanoResult<()> {
         Terminal::execute(cursor_style)?;
         Ok(())
@@ -141,38 +309,209 @@ impl Terminal {
         Ok(())
     }
 
-    /// Hide the cursor
-    pub fn hide_cursor() -> NanoResult<()> {
-        Terminal::execute(cursor::Hide)
-    }
-
     /// Show the cursor
     pub fn show_cursor() -> NanoResult<()> {
         Terminal::execute(cursor::Show)
     }
 
-    /// Clear the current line
-    pub fn clear_current_line() -> NanoResult<()> {
-        Terminal::execute(cterminal::Clear(cterminal::ClearType::CurrentLine))
-    }
-
-    /// Write a string to the terminal
-    pub fn write<S: AsRef<str>>(s: S) {
-        println!("{}\r", s.as_ref());
-    }
-
     /// Clears the terminal
     pub fn clear() -> NanoResult<()> {
         Terminal::execute(cterminal::Clear(cterminal::ClearType::All))
     }
 
-    /// Read a key from the terminal
-    pub fn read_key(&mut self) -> NanoResult<KeyEvent> {
+    /// Read an event from the terminal
+    /// Only key presses, pastes and resizes are reported, every other event is
+    /// discarded. When a timeout is given, this will return `None` if no
+    /// event arrived in time.
+    pub fn read_event(&mut self, timeout: Option<Duration>) -> NanoResult<Option<Event>> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+
         loop {
+            if let Some(deadline) = deadline {
+                if !event::poll(deadline.saturating_duration_since(Instant::now()))? {
+                    return Ok(None);
+                }
+            }
+
             match event::read()? {
-                event::Event::Key(event) => return Ok(event),
+                event @ (Event::Key(_) | Event::Paste(_) | Event::Resize(..)) => {
+                    return Ok(Some(event))
+                }
                 _ => continue,
             }
         }
     }
 }
+
+/// Get the first column shown of a line whose cursor is at `column`, in a
+/// view `columns` wide
+/// Lines scroll by pages, keeping a few columns of the previous page in
+/// view, and the last column is left for the `>` marking a longer line.
+fn page_start(column: usize, columns: usize) -> usize {
+    if column == 0 || column.saturating_add(2) < columns {
+        0
+    } else if columns > 8 {
+        column - 6 - (column - 6) % (columns - 8)
+    } else {
+        column.saturating_sub(columns.saturating_sub(2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal(width: u16, rows: u16) -> Terminal {
+        let mut terminal = Terminal {
+            width: 0,
+            height: 0,
+            rows: 0,
+            offset: Location::default(),
+            offset_chunk: 0,
+            cursor: Location::default(),
+        };
+        terminal.resize(width, rows);
+        terminal
+    }
+
+    #[test]
+    fn test_resize() {
+        let mut terminal = terminal(80, 24);
+        assert_eq!(terminal.height, 20);
+        assert!(!terminal.is_too_small());
+
+        terminal.resize(80, 4);
+        assert_eq!(terminal.height, 0);
+        assert!(terminal.is_too_small());
+    }
+
+    const SMOOTH: Scrolling = Scrolling {
+        margin: 0,
+        smooth: true,
+    };
+
+    #[test]
+    fn test_scroll_to_cursor() {
+        let mut terminal = terminal(80, 24);
+        terminal.cursor = Location { x: 100, y: 30 };
+        terminal.scroll_to_cursor(100, 80, SMOOTH);
+        assert_eq!((terminal.offset.x, terminal.offset.y), (72, 11));
+
+        terminal.cursor = Location { x: 0, y: 5 };
+        terminal.scroll_to_cursor(100, 80, SMOOTH);
+        assert_eq!((terminal.offset.x, terminal.offset.y), (0, 5));
+
+        // A smaller terminal scrolls again to keep the cursor in view
+        terminal.cursor.y = 19;
+        terminal.resize(80, 14);
+        terminal.scroll_to_cursor(100, 80, SMOOTH);
+        assert_eq!(terminal.offset.y, 10);
+
+        // Nothing is in view of a terminal too small to show the text
+        terminal.resize(80, 4);
+        terminal.scroll_to_cursor(100, 80, SMOOTH);
+        assert_eq!(terminal.offset.y, 10);
+    }
+
+    #[test]
+    fn test_scroll_margin() {
+        let scrolling = Scrolling {
+            margin: 3,
+            smooth: true,
+        };
+        let mut terminal = terminal(80, 24);
+        terminal.cursor.y = 17;
+        terminal.scroll_to_cursor(100, 80, scrolling);
+        assert_eq!(terminal.offset.y, 1);
+
+        terminal.cursor.y = 3;
+        terminal.scroll_to_cursor(100, 80, scrolling);
+        assert_eq!(terminal.offset.y, 0);
+
+        // There is no margin to keep past the end of the buffer
+        terminal.cursor.y = 99;
+        terminal.scroll_to_cursor(100, 80, scrolling);
+        assert_eq!(terminal.offset.y, 80);
+        terminal.cursor.y = 97;
+        terminal.scroll_to_cursor(100, 80, scrolling);
+        assert_eq!(terminal.offset.y, 80);
+    }
+
+    #[test]
+    fn test_jumpy_scrolling() {
+        let mut terminal = terminal(80, 24);
+        terminal.cursor.y = 20;
+        terminal.scroll_to_cursor(100, 80, Scrolling::default());
+        assert_eq!(terminal.offset.y, 10);
+
+        terminal.cursor.y = 29;
+        terminal.scroll_to_cursor(100, 80, Scrolling::default());
+        assert_eq!(terminal.offset.y, 10);
+    }
+
+    #[test]
+    fn test_scroll_to_chunk() {
+        // Lines of three chunks, on a screen of 20 rows
+        let chunks = |_| 3;
+        let mut terminal = terminal(80, 24);
+        terminal.cursor.y = 6;
+        terminal.scroll_to_chunk(2, 100, SMOOTH, chunks);
+        assert_eq!((terminal.offset.y, terminal.offset_chunk), (0, 1));
+
+        terminal.scroll_to_chunk(0, 100, SMOOTH, chunks);
+        assert_eq!((terminal.offset.y, terminal.offset_chunk), (0, 1));
+
+        terminal.cursor.y = 0;
+        terminal.scroll_to_chunk(0, 100, SMOOTH, chunks);
+        assert_eq!((terminal.offset.y, terminal.offset_chunk), (0, 0));
+
+        let scrolling = Scrolling {
+            margin: 2,
+            smooth: true,
+        };
+        terminal.cursor.y = 40;
+        terminal.scroll_to_chunk(1, 100, scrolling, chunks);
+        assert_eq!((terminal.offset.y, terminal.offset_chunk), (34, 2));
+        terminal.scroll_to_chunk(0, 100, scrolling, chunks);
+        assert_eq!((terminal.offset.y, terminal.offset_chunk), (34, 2));
+
+        terminal.cursor.y = 34;
+        terminal.scroll_to_chunk(1, 100, scrolling, chunks);
+        assert_eq!((terminal.offset.y, terminal.offset_chunk), (33, 2));
+
+        // Jumpy scrolling centres the chunk of the cursor
+        terminal.cursor.y = 60;
+        terminal.scroll_to_chunk(0, 100, Scrolling::default(), chunks);
+        assert_eq!((terminal.offset.y, terminal.offset_chunk), (56, 2));
+    }
+
+    #[test]
+    fn test_scroll_past_u16() {
+        let mut terminal = terminal(80, 24);
+        terminal.cursor = Location {
+            x: 100_000,
+            y: 100_000,
+        };
+        terminal.scroll_to_cursor(200_000, 80, SMOOTH);
+        assert_eq!(terminal.offset.y, 99_981);
+        assert_eq!(terminal.offset.x, page_start(100_000, 80));
+        assert!(terminal.offset.x > u16::MAX as usize);
+
+        terminal.cursor = Location {
+            x: usize::MAX,
+            y: usize::MAX - 1,
+        };
+        terminal.scroll_to_cursor(usize::MAX, 80, Scrolling::default());
+        assert_eq!(terminal.offset.y, usize::MAX - 11);
+    }
+
+    #[test]
+    fn test_page_start() {
+        assert_eq!(page_start(0, 80), 0);
+        assert_eq!(page_start(77, 80), 0);
+        assert_eq!(page_start(78, 80), 72);
+        assert_eq!(page_start(143, 80), 72);
+        assert_eq!(page_start(150, 80), 144);
+        assert_eq!(page_start(10, 6), 6);
+    }
+}
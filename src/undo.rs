@@ -0,0 +1,243 @@
+/// Edit kind
+/// What an edit did. Like in nano, a new undo step starts when the kind of
+/// edit changes, so that typing and deleting are undone apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditKind {
+    /// Text typed on a line
+    Typing,
+    /// Graphemes deleted before the cursor
+    Backspace,
+    /// Graphemes deleted under the cursor
+    Delete,
+    /// A line broken in two or two lines joined, undone on its own
+    Lines,
+}
+
+/// Edit
+/// A change of the buffer: the lines from `row` it replaced and those
+/// replacing them, and where the cursor was before and after it, as
+/// `(column, line)` in graphemes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub kind: EditKind,
+    pub row: usize,
+    pub removed: Vec<String>,
+    pub inserted: Vec<String>,
+    pub before: (usize, usize),
+    pub after: (usize, usize),
+}
+
+impl Edit {
+    /// Get the edit undoing this one
+    pub fn inverse(&self) -> Edit {
+        Edit {
+            kind: self.kind,
+            row: self.row,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+            before: self.after,
+            after: self.before,
+        }
+    }
+
+    /// Check whether an edit goes on typing or deleting on the line of this
+    /// one, so that both are undone together
+    fn continued_by(&self, edit: &Edit) -> bool {
+        self.kind == edit.kind
+            && self.kind != EditKind::Lines
+            && self.row == edit.row
+            && self.after == edit.before
+            && self.inserted.len() == 1
+            && edit.inserted.len() == 1
+            && edit.removed == self.inserted
+    }
+}
+
+/// Undo stack
+/// The edits of the buffer that can be undone (Alt+U) and redone (Alt+E),
+/// like in nano.
+///
+/// Characters typed or deleted one after another on a line are undone
+/// together, while an edit that breaks or joins lines is undone on its own.
+///
+/// The stack also tracks whether undoing and redoing got the buffer back to
+/// the file as it was read.
+#[derive(Debug)]
+pub struct UndoStack {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// The number of edits to undo that leaves the file as it was read,
+    /// `None` once no undoing or redoing can get back to it
+    saved: Option<usize>,
+}
+
+impl Default for UndoStack {
+    fn default() -> Self {
+        Self {
+            undo: Vec::new(),
+            redo: Vec::new(),
+            saved: Some(0),
+        }
+    }
+}
+
+impl UndoStack {
+    /// Record an edit made to the buffer
+    /// The edits undone before it can no longer be redone.
+    pub fn push(&mut self, edit: Edit) {
+        self.redo.clear();
+        // The file as it was read is lost with the edits to redo, or when
+        // the edit leaving it goes on
+        let length = self.undo.len();
+        if self.saved > Some(length) {
+            self.saved = None;
+        }
+        match self.undo.last_mut() {
+            Some(last) if last.continued_by(&edit) => {
+                if self.saved == Some(length) {
+                    self.saved = None;
+                }
+                last.inserted = edit.inserted;
+                last.after = edit.after;
+            }
+            _ => self.undo.push(edit),
+        }
+    }
+
+    /// Take the last edit to undo
+    /// This will return the edit to make to the buffer to undo it.
+    pub fn undo(&mut self) -> Option<Edit> {
+        let edit = self.undo.pop()?;
+        let inverse = edit.inverse();
+        self.redo.push(edit);
+        Some(inverse)
+    }
+
+    /// Take the last edit undone to make it again
+    pub fn redo(&mut self) -> Option<Edit> {
+        let edit = self.redo.pop()?;
+        self.undo.push(edit.clone());
+        Some(edit)
+    }
+
+    /// Check whether the edits made leave the file as it was read
+    pub fn is_saved(&self) -> bool {
+        self.saved == Some(self.undo.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typing(row: usize, from: &str, to: &str, x: usize) -> Edit {
+        Edit {
+            kind: EditKind::Typing,
+            row,
+            removed: vec![from.to_string()],
+            inserted: vec![to.to_string()],
+            before: (x, row),
+            after: (x + 1, row),
+        }
+    }
+
+    fn backspace(row: usize, from: &str, to: &str, x: usize) -> Edit {
+        Edit {
+            kind: EditKind::Backspace,
+            row,
+            removed: vec![from.to_string()],
+            inserted: vec![to.to_string()],
+            before: (x, row),
+            after: (x - 1, row),
+        }
+    }
+
+    #[test]
+    fn test_typing_undone_together() {
+        let mut stack = UndoStack::default();
+        stack.push(typing(0, "", "a", 0));
+        stack.push(typing(0, "a", "ab", 1));
+
+        let undo = stack.undo().unwrap();
+        assert_eq!(undo.removed, ["ab"]);
+        assert_eq!(undo.inserted, [""]);
+        assert_eq!(undo.after, (0, 0));
+        assert!(stack.undo().is_none());
+
+        assert_eq!(stack.redo().unwrap().inserted, ["ab"]);
+        assert!(stack.redo().is_none());
+    }
+
+    #[test]
+    fn test_breaks_undone_alone() {
+        let mut stack = UndoStack::default();
+        stack.push(typing(0, "", "a", 0));
+        stack.push(Edit {
+            kind: EditKind::Lines,
+            row: 0,
+            removed: vec!["a".into()],
+            inserted: vec!["a".into(), "b".into()],
+            before: (1, 0),
+            after: (1, 1),
+        });
+        stack.push(typing(1, "b", "bc", 1));
+        // Typing elsewhere starts a new edit
+        stack.push(typing(0, "a", "xa", 0));
+
+        assert_eq!(stack.undo().unwrap().inserted, ["a"]);
+        assert_eq!(stack.undo().unwrap().inserted, ["b"]);
+        assert_eq!(stack.undo().unwrap().inserted, ["a"]);
+    }
+
+    #[test]
+    fn test_type_then_backspace_undone_apart() {
+        let mut stack = UndoStack::default();
+        stack.push(typing(0, "", "a", 0));
+        stack.push(typing(0, "a", "ab", 1));
+        stack.push(backspace(0, "ab", "a", 2));
+        stack.push(backspace(0, "a", "", 1));
+        stack.push(typing(0, "", "c", 0));
+
+        assert_eq!(stack.undo().unwrap().inserted, [""]);
+        let undo = stack.undo().unwrap();
+        assert_eq!(undo.inserted, ["ab"]);
+        assert_eq!(undo.after, (2, 0));
+        assert_eq!(stack.undo().unwrap().inserted, [""]);
+        assert!(stack.undo().is_none());
+    }
+
+    #[test]
+    fn test_undone_back_to_saved() {
+        let mut stack = UndoStack::default();
+        assert!(stack.is_saved());
+        stack.push(typing(0, "", "a", 0));
+        stack.push(Edit {
+            kind: EditKind::Lines,
+            ..typing(0, "a", "a", 1)
+        });
+        assert!(!stack.is_saved());
+        stack.undo();
+        assert!(!stack.is_saved());
+        stack.undo();
+        assert!(stack.is_saved());
+        stack.redo();
+        assert!(!stack.is_saved());
+        stack.undo();
+        assert!(stack.is_saved());
+
+        // Another edit from the file as read is undone back to it too
+        stack.push(typing(0, "", "b", 0));
+        assert!(!stack.is_saved());
+        stack.undo();
+        assert!(stack.is_saved());
+    }
+
+    #[test]
+    fn test_push_clears_redo() {
+        let mut stack = UndoStack::default();
+        stack.push(typing(0, "", "a", 0));
+        stack.undo();
+        stack.push(typing(0, "", "b", 0));
+        assert!(stack.redo().is_none());
+    }
+}
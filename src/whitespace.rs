@@ -0,0 +1,116 @@
+use crate::content::{self, Glyph, UnicodeSegmentation};
+
+/// Whitespace
+/// How tabs and spaces are shown, like nano's `whitespace` option, which is
+/// toggled with Alt+P.
+///
+/// When shown, a tab starts with its glyph and a space is replaced by its
+/// own. Trailing whitespace can be highlighted whether shown or not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Whitespace {
+    /// Whether tabs and spaces are shown
+    pub shown: bool,
+    /// Whether whitespace at the end of lines is highlighted
+    pub trailing: bool,
+    tab: String,
+    space: String,
+}
+
+impl Default for Whitespace {
+    fn default() -> Self {
+        Self {
+            shown: false,
+            trailing: false,
+            tab: String::from("»"),
+            space: String::from("·"),
+        }
+    }
+}
+
+impl Whitespace {
+    /// Set the glyphs of tabs and spaces, `"»·"` by default
+    /// Both must take a single column.
+    pub fn set_glyphs(&mut self, glyphs: &str) -> Result<(), String> {
+        let single = |glyph: &&str| {
+            matches!(Glyph::new(glyph, 0, 1), Glyph::Text(_)) && content::width(glyph) == 1
+        };
+        match glyphs.graphemes(true).collect::<Vec<_>>()[..] {
+            [tab, space] if single(&tab) && single(&space) => {
+                self.tab = tab.to_string();
+                self.space = space.to_string();
+                Ok(())
+            }
+            _ => Err(format!(
+                "whitespace must be two single-column characters: {:?}",
+                glyphs
+            )),
+        }
+    }
+
+    /// Get the text a tab of `width` columns is shown as
+    pub fn tab(&self, width: usize) -> String {
+        if self.shown {
+            self.tab.clone() + &" ".repeat(width - 1)
+        } else {
+            " ".repeat(width)
+        }
+    }
+
+    /// Get the text a space is shown as
+    pub fn space(&self) -> &str {
+        if self.shown {
+            &self.space
+        } else {
+            " "
+        }
+    }
+}
+
+/// Get the index of the grapheme trailing whitespace starts at in a line
+/// This is the number of graphemes of a line without any.
+pub fn trailing_start(text: &str) -> usize {
+    text.trim_end_matches([' ', '\t']).graphemes(true).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hidden() {
+        let whitespace = Whitespace::default();
+        assert_eq!(whitespace.tab(3), "   ");
+        assert_eq!(whitespace.space(), " ");
+    }
+
+    #[test]
+    fn test_shown() {
+        let mut whitespace = Whitespace {
+            shown: true,
+            ..Default::default()
+        };
+        assert_eq!(whitespace.tab(4), "»   ");
+        assert_eq!(whitespace.space(), "·");
+
+        whitespace.set_glyphs(">.").unwrap();
+        assert_eq!(whitespace.tab(1), ">");
+        assert_eq!(whitespace.space(), ".");
+    }
+
+    #[test]
+    fn test_set_glyphs_errors() {
+        let mut whitespace = Whitespace::default();
+        assert!(whitespace.set_glyphs("»").is_err());
+        assert!(whitespace.set_glyphs("»·-").is_err());
+        assert!(whitespace.set_glyphs("日·").is_err());
+        assert!(whitespace.set_glyphs("\t·").is_err());
+        assert_eq!(whitespace, Whitespace::default());
+    }
+
+    #[test]
+    fn test_trailing_start() {
+        assert_eq!(trailing_start("日本 \t "), 2);
+        assert_eq!(trailing_start("  "), 0);
+        assert_eq!(trailing_start("a b"), 3);
+    }
+}
@@ -0,0 +1,291 @@
+use crate::content::{Data, Glyph, UnicodeSegmentation};
+
+/// Chunk
+/// A visual line of a soft wrapped line: the grapheme it starts at and the
+/// display column of that grapheme in the whole line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Chunk {
+    pub start: usize,
+    pub column: usize,
+}
+
+/// Soft wrap
+/// Whether lines longer than the view are shown across several rows of the
+/// screen, like nano's `softwrap` option, which is toggled with Alt+S.
+///
+/// Lines are broken at the last column by default, or after the last blank
+/// that fits with `at_blanks`, like nano's `atblanks`. A word longer than a
+/// row is broken at the last column either way.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SoftWrap {
+    /// Whether long lines are wrapped
+    pub enabled: bool,
+    /// Whether lines are broken after blanks rather than at the last column
+    pub at_blanks: bool,
+}
+
+impl SoftWrap {
+    /// Break a line into the chunks shown in rows `width` columns wide
+    /// A line filling its last row exactly gets an empty chunk after it,
+    /// where the cursor is shown at the end of the line.
+    pub fn chunks(&self, content: &Data, width: usize, tabsize: usize) -> Vec<Chunk> {
+        let width = width.max(1);
+        let mut chunks = vec![Chunk::default()];
+        // Where the chunk can be broken after a blank
+        let mut blank = None;
+
+        let mut column = 0;
+        for (index, grapheme) in content.text.graphemes(true).enumerate() {
+            let glyph = Glyph::new(grapheme, column, tabsize).width();
+            let mut chunk = chunks[chunks.len() - 1];
+            while column + glyph > chunk.column + width && index > chunk.start {
+                chunk = match blank {
+                    Some(blank @ Chunk { start, .. }) if self.at_blanks && start > chunk.start => {
+                        blank
+                    }
+                    _ => Chunk {
+                        start: index,
+                        column,
+                    },
+                };
+                chunks.push(chunk);
+            }
+
+            column += glyph;
+            if is_blank(grapheme) {
+                blank = Some(Chunk {
+                    start: index + 1,
+                    column,
+                });
+            }
+        }
+
+        let last = chunks[chunks.len() - 1];
+        if column > 0 && column == last.column + width {
+            chunks.push(Chunk {
+                start: content.grapheme_count(),
+                column,
+            });
+        }
+        chunks
+    }
+}
+
+/// Hard wrap
+/// Whether a line typed past the fill column is broken in two, like nano's
+/// `breaklonglines`, which is toggled with Alt+L.
+///
+/// The line is broken at its last blank before the fill column, or at the
+/// first one after it when a word goes past the column, and the new line
+/// gets the indentation of the broken one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HardWrap {
+    /// Whether long lines are broken while typing
+    pub enabled: bool,
+    /// The column past which lines are broken
+    pub fill: usize,
+}
+
+impl Default for HardWrap {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fill: 72,
+        }
+    }
+}
+
+impl HardWrap {
+    /// Break a line typed past the fill column
+    /// The blanks at the break are dropped. This will return the lines
+    /// replacing the line, and where the cursor at grapheme `cursor` moves
+    /// to as `(column, line)`, the line being 0 or 1. `None` is returned
+    /// when the line fits or has no blank to break it at.
+    pub fn wrap(
+        &self,
+        line: &str,
+        cursor: usize,
+        tabsize: usize,
+    ) -> Option<(Vec<String>, (usize, usize))> {
+        if !self.enabled || Data::from(line).width(tabsize) <= self.fill {
+            return None;
+        }
+        let indent = &line[..line.len() - line.trim_start_matches(is_blank_char).len()];
+
+        // The runs of blanks followed by text, with the column they start at
+        let mut breaks = Vec::new();
+        let mut run = None;
+        let mut column = 0;
+        for (offset, grapheme) in line.grapheme_indices(true) {
+            if is_blank(grapheme) {
+                if offset >= indent.len() {
+                    run.get_or_insert((column, offset));
+                }
+            } else if let Some((start_column, start)) = run.take() {
+                breaks.push((start_column, start, offset));
+            }
+            column += Glyph::new(grapheme, column, tabsize).width();
+        }
+        let &(_, start, end) = breaks
+            .iter()
+            .rev()
+            .find(|(column, ..)| *column <= self.fill)
+            .or(breaks.first())?;
+
+        let graphemes = |text: &str| text.graphemes(true).count();
+        let (start_index, end_index) = (graphemes(&line[..start]), graphemes(&line[..end]));
+        let cursor = if cursor <= start_index {
+            (cursor, 0)
+        } else {
+            (cursor.max(end_index) - end_index + graphemes(indent), 1)
+        };
+        let lines = vec![
+            line[..start].to_string(),
+            format!("{}{}", indent, &line[end..]),
+        ];
+        Some((lines, cursor))
+    }
+}
+
+/// Check whether a grapheme is a blank, which lines can be broken at
+fn is_blank(grapheme: &str) -> bool {
+    grapheme.chars().all(is_blank_char)
+}
+
+/// Check whether a character is a blank
+/// Any Unicode white space is, except the no-break spaces.
+fn is_blank_char(c: char) -> bool {
+    c.is_whitespace() && !matches!(c, '\u{a0}' | '\u{2007}' | '\u{202f}')
+}
+
+/// Get the index of the chunk a grapheme is shown in
+pub fn chunk_of(chunks: &[Chunk], index: usize) -> usize {
+    chunks
+        .iter()
+        .rposition(|chunk| chunk.start <= index)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn starts(wrap: SoftWrap, text: &str, width: usize) -> Vec<(usize, usize)> {
+        wrap.chunks(&Data::from(text), width, 4)
+            .iter()
+            .map(|chunk| (chunk.start, chunk.column))
+            .collect()
+    }
+
+    const AT_COLUMN: SoftWrap = SoftWrap {
+        enabled: true,
+        at_blanks: false,
+    };
+    const AT_BLANKS: SoftWrap = SoftWrap {
+        enabled: true,
+        at_blanks: true,
+    };
+
+    #[test]
+    fn test_chunks_at_column() {
+        assert_eq!(starts(AT_COLUMN, "", 4), [(0, 0)]);
+        assert_eq!(starts(AT_COLUMN, "abc", 4), [(0, 0)]);
+        assert_eq!(starts(AT_COLUMN, "abcdefghij", 4), [(0, 0), (4, 4), (8, 8)]);
+        // The cursor after a full last row goes on a row of its own
+        assert_eq!(starts(AT_COLUMN, "abcdefgh", 4), [(0, 0), (4, 4), (8, 8)]);
+    }
+
+    #[test]
+    fn test_chunks_wide_and_tabs() {
+        // A wide grapheme that does not fit goes to the next row
+        assert_eq!(starts(AT_COLUMN, "a日本語", 4), [(0, 0), (2, 3), (4, 7)]);
+        assert_eq!(starts(AT_COLUMN, "ab\tcd", 4), [(0, 0), (3, 4)]);
+    }
+
+    #[test]
+    fn test_chunks_at_blanks() {
+        assert_eq!(starts(AT_BLANKS, "ab cd ef", 6), [(0, 0), (6, 6)]);
+        assert_eq!(
+            starts(AT_BLANKS, "abc defgh ij", 7),
+            [(0, 0), (4, 4), (10, 10)]
+        );
+        // Words longer than a row are broken at the last column
+        assert_eq!(
+            starts(AT_BLANKS, "a bcdefghij", 4),
+            [(0, 0), (2, 2), (6, 6), (10, 10)]
+        );
+    }
+
+    #[test]
+    fn test_hard_wrap() {
+        let wrap = HardWrap {
+            enabled: true,
+            fill: 10,
+        };
+        assert_eq!(
+            wrap.wrap("hello world foo", 15, 8),
+            Some((vec!["hello".into(), "world foo".into()], (9, 1)))
+        );
+        // The cursor before the break stays on the first line
+        assert_eq!(wrap.wrap("hello world foo", 2, 8).unwrap().1, (2, 0));
+        assert_eq!(wrap.wrap("short", 5, 8), None);
+        assert_eq!(wrap.wrap("abcdefghijkl", 12, 8), None);
+        assert_eq!(HardWrap::default().wrap("hello world foo", 15, 8), None);
+    }
+
+    #[test]
+    fn test_hard_wrap_cursor_in_blanks() {
+        let wrap = HardWrap {
+            enabled: true,
+            fill: 10,
+        };
+        let lines = vec!["hello".to_string(), "world foo".to_string()];
+        // A cursor at the start of the blanks stays at the end of the first
+        // line, and one inside or after them goes to the start of the second
+        assert_eq!(wrap.wrap("hello   world foo", 5, 8), Some((lines, (5, 0))));
+        assert_eq!(wrap.wrap("hello   world foo", 6, 8).unwrap().1, (0, 1));
+        assert_eq!(wrap.wrap("hello   world foo", 7, 8).unwrap().1, (0, 1));
+        assert_eq!(wrap.wrap("hello   world foo", 8, 8).unwrap().1, (0, 1));
+        assert_eq!(wrap.wrap("hello   world foo", 9, 8).unwrap().1, (1, 1));
+    }
+
+    #[test]
+    fn test_hard_wrap_unicode_blanks() {
+        let wrap = HardWrap {
+            enabled: true,
+            fill: 6,
+        };
+        // An ideographic space indents and an em space separates words
+        assert_eq!(
+            wrap.wrap("\u{3000}ab\u{2003}cd ef", 9, 8),
+            Some((vec!["\u{3000}ab".into(), "\u{3000}cd ef".into()], (6, 1)))
+        );
+        // A no-break space does not
+        assert_eq!(wrap.wrap("ab\u{a0}cd\u{a0}ef", 8, 8), None);
+    }
+
+    #[test]
+    fn test_hard_wrap_keeps_indentation() {
+        let wrap = HardWrap {
+            enabled: true,
+            fill: 6,
+        };
+        assert_eq!(
+            wrap.wrap("  ab cd ef", 10, 8),
+            Some((vec!["  ab".into(), "  cd ef".into()], (7, 1)))
+        );
+        // A word past the fill column is broken after
+        assert_eq!(
+            wrap.wrap("abcdefghij   kl", 15, 8),
+            Some((vec!["abcdefghij".into(), "kl".into()], (2, 1)))
+        );
+    }
+
+    #[test]
+    fn test_chunk_of() {
+        let chunks = AT_COLUMN.chunks(&Data::from("abcdefgh"), 4, 4);
+        assert_eq!(chunk_of(&chunks, 0), 0);
+        assert_eq!(chunk_of(&chunks, 5), 1);
+        assert_eq!(chunk_of(&chunks, 8), 2);
+    }
+}